@@ -1,3 +1,4 @@
+use stack_graph_tools::{ExportFormat, Exporter};
 use stack_graphs::graph::{StackGraph, NodeID};
 
 fn main() {
@@ -20,6 +21,9 @@ fn main() {
     println!("  - Symbols: A, foo");
     println!("  - Scope node: {:?}", scope_node);
     
+    // Print the graph in canonical text form
+    print!("\n{}", Exporter::new(&graph).render(ExportFormat::Text));
+    
     // Print completion message
     println!("\nStack-Graphs Example Completed");
     println!("Note: This is a minimal setup demonstrating basic graph construction.");
@@ -0,0 +1,46 @@
+//! Generates one test per fixture for `tests/assertions_test.rs`
+//!
+//! Every file or directory directly inside the fixture directories becomes a
+//! `#[test]` calling `check_fixture`, so adding a fixture needs no change to the
+//! test file.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Fixture directories below `tests/fixtures`
+const FIXTURE_DIRS: &[&str] = &["python", "assertions"];
+
+fn main() {
+    let fixtures = Path::new("tests/fixtures");
+    let mut tests = String::new();
+    for dir in FIXTURE_DIRS {
+        // 追加や削除でディレクトリの更新時刻が変わるので、それを監視する
+        println!("cargo:rerun-if-changed={}", fixtures.join(dir).display());
+
+        let mut cases = fs::read_dir(fixtures.join(dir))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        cases.sort();
+        for case in cases {
+            tests.push_str(&format!(
+                "#[test]\nfn test_{}_{}() {{\n    check_fixture({:?}, {:?});\n}}\n\n",
+                dir,
+                test_name(&case),
+                dir,
+                case
+            ));
+        }
+    }
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("fixture_tests.rs");
+    fs::write(out, tests).unwrap();
+}
+
+/// ファイル名をテスト関数名に使える形にする
+fn test_name(case: &str) -> String {
+    case.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
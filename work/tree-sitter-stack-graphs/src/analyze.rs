@@ -0,0 +1,297 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use rayon::prelude::*;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{File, StackGraph};
+use stack_graphs::CancellationError;
+use tree_sitter_stack_graphs::{
+    BuildError, CancellationFlag, Variables, FILE_PATH_VAR, ROOT_PATH_VAR,
+};
+
+use crate::cancel::FileCancellation;
+use crate::diagnostics::{self, SyntaxError};
+use crate::error::Error;
+use crate::files;
+use crate::rules::{Language, LanguageRegistry};
+
+/// Error reported for files whose extension belongs to no registered language
+pub const UNKNOWN_LANGUAGE: &str = "no language is registered for this file extension";
+
+/// Result of building the stack graph for a single file
+pub enum FileOutcome {
+    /// The file was added to the graph with the given number of nodes; in
+    /// tolerant mode, the syntax errors whose code was left out
+    Built {
+        nodes: usize,
+        syntax_errors: Vec<SyntaxError>,
+    },
+    /// Reading, parsing or running the TSG rules failed
+    Failed { error: Error },
+    /// Building the file took longer than the per-file timeout
+    TimedOut,
+}
+
+impl FileOutcome {
+    /// Returns whether the file did not make it into the graph
+    pub fn is_failure(&self) -> bool {
+        !matches!(self, FileOutcome::Built { .. })
+    }
+}
+
+/// Per-file report produced by [`Indexer::build`](crate::Indexer::build)
+pub struct FileReport {
+    pub path: PathBuf,
+    pub outcome: FileOutcome,
+}
+
+/// Options for [`Indexer::build`](crate::Indexer::build)
+#[derive(Clone, Copy, Default)]
+pub struct BuildOptions {
+    /// Continue with the remaining files after a failure
+    pub keep_going: bool,
+    /// Give up on a file after this long
+    pub timeout: Option<Duration>,
+    /// Build the well-formed parts of files with syntax errors instead of
+    /// failing them; statements around syntax errors are blanked before building
+    pub tolerant: bool,
+}
+
+/// Builds a single stack graph out of all given files
+///
+/// Every file is built into its own `StackGraph` on the rayon thread pool, and
+/// the per-file graphs are merged in file order afterwards, after the fragments
+/// of the languages' builtins stubs.
+///
+/// # Arguments
+/// * `languages` - The languages whose TSG rules are run, chosen per file by
+///   its extension
+/// * `roots` - The files and directories the files were collected from, which
+///   determine the project root of each file
+/// * `files` - Source files to add to the graph
+/// * `options` - Failure handling and per-file timeout
+///
+/// # Returns
+/// The combined graph together with one report per processed file. Without
+/// `keep_going` the first failure cancels the files still being built, and
+/// reports stop after it. Files cancelled that way or by Ctrl-C are not reported.
+pub fn build_graph(
+    languages: &LanguageRegistry,
+    roots: &[PathBuf],
+    files: &[PathBuf],
+    options: BuildOptions,
+) -> (StackGraph, Vec<FileReport>) {
+    let stop = AtomicBool::new(false);
+    let results = files
+        .par_iter()
+        .map(|path| {
+            let cancellation = FileCancellation::new(options.timeout, &stop);
+            let root = files::source_root(roots, path);
+            let result = build_file(languages, path, &root, options.tolerant, &cancellation);
+            if !options.keep_going && matches!(result, FileBuild::Failed(_) | FileBuild::TimedOut) {
+                stop.store(true, Ordering::Relaxed);
+            }
+            result
+        })
+        .collect::<Vec<_>>();
+
+    // 組み込み名の後にファイル順でグラフをマージする
+    let mut stack_graph = StackGraph::new();
+    languages.add_builtins(&mut stack_graph);
+    let mut reports = Vec::new();
+    for (path, result) in files.iter().zip(results) {
+        let outcome = match result {
+            FileBuild::Built {
+                graph,
+                nodes,
+                syntax_errors,
+            } => match stack_graph.add_from_graph(&graph) {
+                Ok(_) => FileOutcome::Built {
+                    nodes,
+                    syntax_errors,
+                },
+                Err(_) => FileOutcome::Failed {
+                    error: Error::Build {
+                        path: path.clone(),
+                        message: "file is already part of the graph".to_string(),
+                    },
+                },
+            },
+            FileBuild::Failed(error) => FileOutcome::Failed { error },
+            FileBuild::TimedOut => FileOutcome::TimedOut,
+            FileBuild::Cancelled => continue,
+        };
+        let failed = outcome.is_failure();
+        reports.push(FileReport {
+            path: path.clone(),
+            outcome,
+        });
+        if failed && !options.keep_going {
+            break;
+        }
+    }
+
+    (stack_graph, reports)
+}
+
+/// 1ファイル分の構築結果
+enum FileBuild {
+    Built {
+        // 他の結果と大きさを揃えるため箱に入れる
+        graph: Box<StackGraph>,
+        nodes: usize,
+        syntax_errors: Vec<SyntaxError>,
+    },
+    Failed(Error),
+    TimedOut,
+    Cancelled,
+}
+
+/// 1ファイル分のグラフを独立した StackGraph に構築する
+fn build_file(
+    languages: &LanguageRegistry,
+    path: &Path,
+    root: &Path,
+    tolerant: bool,
+    cancellation: &FileCancellation,
+) -> FileBuild {
+    if cancellation.stopped() {
+        return FileBuild::Cancelled;
+    }
+    let Some(language) = languages.for_path(path) else {
+        return FileBuild::Failed(Error::Build {
+            path: path.to_path_buf(),
+            message: UNKNOWN_LANGUAGE.to_string(),
+        });
+    };
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => return FileBuild::Failed(Error::io(path, err)),
+    };
+
+    let mut graph = StackGraph::new();
+    let result = if tolerant {
+        build_source_tolerant(language, &mut graph, path, root, &source, cancellation)
+    } else {
+        build_source(language, &mut graph, path, root, &source, cancellation)
+            .map(|file| (file, Vec::new()))
+    };
+    match result {
+        Ok((file, syntax_errors)) => {
+            let nodes = graph.nodes_for_file(file).count();
+            FileBuild::Built {
+                graph: Box::new(graph),
+                nodes,
+                syntax_errors,
+            }
+        }
+        Err(Error::Cancelled(_)) if cancellation.timed_out() => FileBuild::TimedOut,
+        Err(Error::Cancelled(_)) => FileBuild::Cancelled,
+        Err(err) => FileBuild::Failed(err),
+    }
+}
+
+/// Runs the TSG rules on already loaded source and adds the result to the graph
+///
+/// The rules see the file's path relative to its project root in the
+/// `FILE_PATH` global and the root itself in `ROOT_PATH`, from which they derive
+/// the module path the file's definitions are exported under.
+///
+/// # Arguments
+/// * `language` - The language whose TSG rules are run
+/// * `stack_graph` - The graph the file's nodes are added to
+/// * `path` - Path of the file, used as its name in the graph
+/// * `root` - Project root of the file, see [`files::source_root`]
+/// * `source` - Contents of the file
+/// * `cancellation` - Checked while the rules run
+///
+/// # Returns
+/// The handle of the file in the graph. Syntax errors are reported as
+/// [`Error::Parse`] and failing rules as [`Error::Execution`], both with the
+/// offending source lines; cancellation is reported as [`Error::Cancelled`].
+pub fn build_source(
+    language: &Language,
+    stack_graph: &mut StackGraph,
+    path: &Path,
+    root: &Path,
+    source: &str,
+    cancellation: &dyn CancellationFlag,
+) -> Result<Handle<File>, Error> {
+    let file_handle = stack_graph.get_or_create_file(&path.to_string_lossy());
+
+    // グローバル変数（ルートからの相対パスとルート）を設定
+    let mut globals = Variables::new();
+    globals
+        .add(
+            FILE_PATH_VAR.into(),
+            files::relative_path(root, path).into(),
+        )
+        .expect("FILE_PATH is set once");
+    globals
+        .add(
+            ROOT_PATH_VAR.into(),
+            root.to_string_lossy().to_string().into(),
+        )
+        .expect("ROOT_PATH is set once");
+
+    language
+        .stack_graph_language
+        .build_stack_graph_into(stack_graph, file_handle, source, &globals, cancellation)
+        .map_err(|err| build_error(language, path, source, err))?;
+
+    Ok(file_handle)
+}
+
+/// Like [`build_source`], but builds a file with syntax errors from its well-formed parts
+///
+/// If the file does not parse cleanly, the code of its syntax errors is blanked
+/// out with [`diagnostics::blank_syntax_errors`] and the rules run on the rest,
+/// so that the nodes keep their positions in the original source.
+///
+/// # Returns
+/// The handle of the file together with the syntax errors of the original
+/// source, which are empty if it parsed cleanly. Files that cannot be blanked
+/// into a clean parse still fail with [`Error::Parse`].
+pub fn build_source_tolerant(
+    language: &Language,
+    stack_graph: &mut StackGraph,
+    path: &Path,
+    root: &Path,
+    source: &str,
+    cancellation: &dyn CancellationFlag,
+) -> Result<(Handle<File>, Vec<SyntaxError>), Error> {
+    match build_source(language, stack_graph, path, root, source, cancellation) {
+        Err(Error::Parse { path, errors }) => {
+            let Some(blanked) = diagnostics::blank_syntax_errors(&language.grammar, source) else {
+                return Err(Error::Parse { path, errors });
+            };
+            let file = build_source(language, stack_graph, &path, root, &blanked, cancellation)?;
+            Ok((file, errors))
+        }
+        result => result.map(|file| (file, Vec::new())),
+    }
+}
+
+/// 上流の BuildError を種類ごとのエラーに変換する
+fn build_error(language: &Language, path: &Path, source: &str, error: BuildError) -> Error {
+    match error {
+        BuildError::Cancelled(reason) => Error::Cancelled(CancellationError(reason)),
+        BuildError::ParseError | BuildError::ParseErrors(_) => Error::Parse {
+            path: path.to_path_buf(),
+            errors: diagnostics::syntax_errors(&language.grammar, path, source),
+        },
+        BuildError::ExecutionError(_) => Error::Execution {
+            path: path.to_path_buf(),
+            message: error.to_string(),
+            rendered: error
+                .display_pretty(path, source, &language.rules_path, &language.rules)
+                .to_string(),
+        },
+        error => Error::Build {
+            path: path.to_path_buf(),
+            message: error.to_string(),
+        },
+    }
+}
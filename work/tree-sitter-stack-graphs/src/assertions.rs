@@ -0,0 +1,276 @@
+//! Resolution assertions embedded in source fixtures
+//!
+//! A fixture is an ordinary source file with assertion comments below the lines
+//! they check. The caret marks the column of a reference in the closest line
+//! above that is not itself an assertion, and `defined:` lists the lines of the
+//! definitions the reference must resolve to, in the same file unless prefixed
+//! with a file name as in `defined: pkg/util.py:3`. File names are relative to
+//! the project root, i.e. the fixture directory being checked:
+//!
+//! ```python
+//! def greet(name):
+//!     return name
+//!     #      ^ defined: 1
+//!
+//! print(greet)
+//! #     ^ defined: 1
+//! undefined
+//! # ^ defined:
+//! ```
+//!
+//! An empty list asserts that the reference does not resolve at all. Assertions
+//! may also start with `//` instead of `#`, for languages with C-style comments.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use stack_graphs::graph::StackGraph;
+
+use crate::analyze::{self, BuildOptions, FileOutcome};
+use crate::files;
+use crate::query::{self, Location};
+use crate::rules::LanguageRegistry;
+
+/// One `# ^ defined: ...` comment
+#[derive(Debug, PartialEq, Eq)]
+pub struct Assertion {
+    /// 1-based line of the assertion comment
+    pub line: usize,
+    /// 1-based line of the reference
+    pub target_line: usize,
+    /// 1-based column of the reference (UTF-8 byte offset within the line)
+    pub target_column: usize,
+    /// Expected definitions as file name and 1-based line; `None` for the fixture's own file
+    pub expected: BTreeSet<(Option<String>, usize)>,
+}
+
+/// An assertion that does not hold, or a fixture that could not be checked
+#[derive(Debug)]
+pub struct AssertionFailure {
+    pub file: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssertionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.message)
+    }
+}
+
+/// Outcome of checking one fixture file
+pub struct FixtureReport {
+    pub file: PathBuf,
+    /// Number of assertions found in the file
+    pub assertions: usize,
+    pub failures: Vec<AssertionFailure>,
+}
+
+/// Extracts the assertions from a fixture's source
+///
+/// # Returns
+/// The assertions in source order, or the 1-based line and message of the first
+/// malformed assertion
+pub fn parse_assertions(source: &str) -> Result<Vec<Assertion>, (usize, String)> {
+    let mut assertions = Vec::new();
+    let mut target_line = None;
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let Some((caret, rest)) = split_assertion(text) else {
+            target_line = Some(line);
+            continue;
+        };
+        let Some(target_line) = target_line else {
+            return Err((line, "assertion before the first source line".to_string()));
+        };
+
+        let Some(list) = rest.trim().strip_prefix("defined:") else {
+            return Err((line, format!("expected `defined:`, got `{}`", rest.trim())));
+        };
+        let mut expected = BTreeSet::new();
+        for item in list
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+        {
+            expected.insert(parse_expected(item).map_err(|err| (line, err))?);
+        }
+
+        assertions.push(Assertion {
+            line,
+            target_line,
+            target_column: caret + 1,
+            expected,
+        });
+    }
+    Ok(assertions)
+}
+
+/// `# ^ ...` 形式の行ならキャレットの位置と残りを返す
+fn split_assertion(text: &str) -> Option<(usize, &str)> {
+    let text_start = text.trim_start();
+    let comment = text_start
+        .strip_prefix('#')
+        .or_else(|| text_start.strip_prefix("//"))?;
+    let caret = comment.trim_start().strip_prefix('^')?;
+    Some((text.len() - caret.len() - 1, caret))
+}
+
+/// `3` または `path/to/file.py:3` を読む
+fn parse_expected(item: &str) -> Result<(Option<String>, usize), String> {
+    let (file, line) = match item.rsplit_once(':') {
+        Some((file, line)) => (Some(file.to_string()), line),
+        None => (None, item),
+    };
+    match line.parse::<usize>() {
+        Ok(line) if line > 0 => Ok((file, line)),
+        _ => Err(format!("invalid definition line `{}`", item)),
+    }
+}
+
+/// Builds the fixture files into one graph and checks all of their assertions
+///
+/// Files of the same project (e.g. a package whose modules import each other)
+/// must be checked together, so that references can resolve across them.
+///
+/// # Arguments
+/// * `languages` - The languages whose TSG rules are under test
+/// * `roots` - The files and directories the fixtures were collected from
+/// * `files` - Fixture files, all of which are added to the graph
+///
+/// # Returns
+/// One report per file, in the given order
+pub fn check_fixtures(
+    languages: &LanguageRegistry,
+    roots: &[PathBuf],
+    files: &[PathBuf],
+) -> Vec<FixtureReport> {
+    let options = BuildOptions {
+        keep_going: true,
+        ..BuildOptions::default()
+    };
+    let (graph, reports) = analyze::build_graph(languages, roots, files, options);
+
+    let mut fixtures = Vec::new();
+    for report in reports {
+        let error = match report.outcome {
+            FileOutcome::Built { .. } => None,
+            FileOutcome::Failed { error } => Some(error.message()),
+            FileOutcome::TimedOut => Some("timed out".to_string()),
+        };
+        if let Some(error) = error {
+            fixtures.push(FixtureReport {
+                failures: vec![AssertionFailure {
+                    file: report.path.clone(),
+                    line: 1,
+                    message: error,
+                }],
+                file: report.path,
+                assertions: 0,
+            });
+            continue;
+        }
+        let root = files::source_root(roots, &report.path);
+        fixtures.push(check_file(&graph, &root, &report.path));
+    }
+    fixtures
+}
+
+/// ファイル1つ分のアサーションを検証する。定義のファイル名はルートからの相対パスで比べる
+fn check_file(graph: &StackGraph, root: &Path, path: &Path) -> FixtureReport {
+    let mut report = FixtureReport {
+        file: path.to_path_buf(),
+        assertions: 0,
+        failures: Vec::new(),
+    };
+    let failure = |line: usize, message: String| AssertionFailure {
+        file: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    let assertions = match fs::read_to_string(path).map_err(|err| (1, err.to_string())) {
+        Ok(source) => parse_assertions(&source),
+        Err(err) => Err(err),
+    };
+    let assertions = match assertions {
+        Ok(assertions) => assertions,
+        Err((line, message)) => {
+            report.failures.push(failure(line, message));
+            return report;
+        }
+    };
+    report.assertions = assertions.len();
+
+    let file_name = path.to_string_lossy();
+    let Some(file) = graph.get_file(&file_name) else {
+        report
+            .failures
+            .push(failure(1, "file is not in the graph".to_string()));
+        return report;
+    };
+
+    for assertion in assertions {
+        let Some(reference) =
+            query::reference_at(graph, file, assertion.target_line, assertion.target_column)
+        else {
+            report.failures.push(failure(
+                assertion.line,
+                format!(
+                    "no reference at {}:{}",
+                    assertion.target_line, assertion.target_column
+                ),
+            ));
+            continue;
+        };
+        let symbol = query::node_symbol(graph, reference).unwrap_or_default();
+
+        let definitions = match query::find_definitions(graph, reference) {
+            Ok(definitions) => definitions,
+            Err(err) => {
+                report
+                    .failures
+                    .push(failure(assertion.line, format!("cancelled at {}", err.0)));
+                continue;
+            }
+        };
+        let actual = definitions
+            .into_iter()
+            .filter_map(|node| Location::of_node(graph, node))
+            .map(|location| {
+                // 同じファイルの定義はファイル名を省いて比べる
+                let file = (location.file != file_name)
+                    .then(|| files::relative_path(root, Path::new(&location.file)));
+                (file, location.start_line)
+            })
+            .collect::<BTreeSet<_>>();
+
+        if actual != assertion.expected {
+            report.failures.push(failure(
+                assertion.line,
+                format!(
+                    "`{}` at {}:{} resolves to [{}], expected [{}]",
+                    symbol,
+                    assertion.target_line,
+                    assertion.target_column,
+                    format_lines(&actual),
+                    format_lines(&assertion.expected)
+                ),
+            ));
+        }
+    }
+    report
+}
+
+fn format_lines(lines: &BTreeSet<(Option<String>, usize)>) -> String {
+    lines
+        .iter()
+        .map(|(file, line)| match file {
+            Some(file) => format!("{}:{}", file, line),
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
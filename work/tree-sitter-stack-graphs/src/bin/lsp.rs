@@ -0,0 +1,46 @@
+//! Standalone language server, the same as `tree-sitter-stack-graphs lsp`
+//!
+//! Editors launch it without arguments and speak LSP over stdin and stdout;
+//! the options select the rules and builtins like those of the main binary.
+
+use std::process::ExitCode;
+
+use clap::Parser;
+
+use stack_graph_tools::{Indexer, LanguageFile};
+
+/// Command line interface of the language server
+#[derive(Parser)]
+#[command(
+    name = "tree-sitter-stack-graphs-lsp",
+    about = "Language server for Python and JavaScript sources over stdio"
+)]
+struct Cli {
+    /// TSG rules file to use instead of the built-in rules of a language, as
+    /// `LANGUAGE=FILE`; a bare `FILE` replaces the Python rules
+    #[arg(long, value_name = "[LANGUAGE=]FILE")]
+    rules: Vec<LanguageFile>,
+
+    /// Add synthetic definitions for builtin names and standard library
+    /// modules before the source files, so that references to them resolve
+    #[arg(long)]
+    builtins: bool,
+
+    /// Builtins stub to use instead of the built-in one of a language, as
+    /// `LANGUAGE=FILE`; a bare `FILE` replaces the Python stub. Implies `--builtins`
+    #[arg(long, value_name = "[LANGUAGE=]FILE")]
+    builtins_stub: Vec<LanguageFile>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = Indexer::with_rules(&cli.rules, cli.builtins, &cli.builtins_stub)
+        .and_then(|indexer| indexer.serve_lsp());
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err.display_pretty());
+            ExitCode::FAILURE
+        }
+    }
+}
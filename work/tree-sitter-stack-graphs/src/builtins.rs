@@ -0,0 +1,214 @@
+//! Synthetic definitions for names that no source file defines
+//!
+//! A builtins stub lists the names that are visible in every module, followed
+//! by sections with the members of standard library modules:
+//!
+//! ```text
+//! # Visible in every module
+//! print
+//! len
+//!
+//! [sys]
+//! path
+//! argv
+//!
+//! [os.path]
+//! join
+//! ```
+//!
+//! [`Stub::add_to_graph`] turns a stub into a graph fragment in a file of its
+//! own. The names before the first section are definitions below a
+//! [`BUILTINS_SYMBOL`] pop node at the root, which the rules push when a name is
+//! not found in a module. Modules are exported from the root under their dotted
+//! path, the same way the rules export source modules, so `import sys` followed
+//! by `sys.path` resolves into the stub. Every definition spans its name in the
+//! stub, so resolved references point at the stub line.
+
+use std::ops::Range;
+
+use lsp_positions::{Offset, Position, Span};
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{File, Node, StackGraph};
+
+/// Symbol that the rules push to look a name up in the builtins
+pub const BUILTINS_SYMBOL: &str = "<builtins>";
+
+/// A name in the stub with its 0-based line and byte range
+#[derive(Debug)]
+struct Name {
+    text: String,
+    line: usize,
+    columns: Range<usize>,
+}
+
+/// `[module.path]` セクションとそのメンバー
+#[derive(Debug)]
+struct Module {
+    path: Vec<Name>,
+    members: Vec<Name>,
+}
+
+/// A parsed builtins stub
+#[derive(Debug)]
+pub struct Stub {
+    file_name: String,
+    source: String,
+    globals: Vec<Name>,
+    modules: Vec<Module>,
+}
+
+impl Stub {
+    /// Parses a stub
+    ///
+    /// # Arguments
+    /// * `file_name` - Name of the file the fragment is added to
+    /// * `source` - Contents of the stub
+    ///
+    /// # Returns
+    /// The stub, or the 1-based line and message of the first malformed line
+    pub fn parse(file_name: &str, source: &str) -> Result<Stub, (usize, String)> {
+        let mut globals = Vec::new();
+        let mut modules: Vec<Module> = Vec::new();
+        for (line, text) in source.lines().enumerate() {
+            let trimmed = text.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let start = text.len() - text.trim_start().len();
+
+            if let Some(header) = trimmed.strip_prefix('[') {
+                let Some(path) = header.strip_suffix(']') else {
+                    return Err((line + 1, format!("unclosed section `{}`", trimmed)));
+                };
+                // 各要素の列はヘッダーの `[` の次から数える
+                let mut column = start + 1;
+                let mut components = Vec::new();
+                for component in path.split('.') {
+                    components.push(name(component, line, column)?);
+                    column += component.len() + 1;
+                }
+                modules.push(Module {
+                    path: components,
+                    members: Vec::new(),
+                });
+                continue;
+            }
+
+            let member = name(trimmed, line, start)?;
+            match modules.last_mut() {
+                Some(module) => module.members.push(member),
+                None => globals.push(member),
+            }
+        }
+        Ok(Stub {
+            file_name: file_name.to_string(),
+            source: source.to_string(),
+            globals,
+            modules,
+        })
+    }
+
+    /// Returns the name of the file the fragment is added to
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// Returns the contents of the stub
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Adds the fragment described in the module documentation to a graph
+    ///
+    /// # Returns
+    /// The handle of the stub's file in the graph
+    pub fn add_to_graph(&self, graph: &mut StackGraph) -> Handle<File> {
+        let file = graph.get_or_create_file(&self.file_name);
+        let root = StackGraph::root_node();
+
+        // どのモジュールからも見える名前
+        let builtins = add_pop(graph, file, BUILTINS_SYMBOL, None);
+        graph.add_edge(root, builtins, 0);
+        let scope = add_scope(graph, file);
+        graph.add_edge(builtins, scope, 0);
+        for global in &self.globals {
+            let definition = add_pop(graph, file, &global.text, Some(global));
+            graph.add_edge(scope, definition, 0);
+        }
+
+        // モジュールはパスの各要素と `.` をルートから順に pop する。最後の要素だけが定義になる
+        for module in &self.modules {
+            let mut parent = root;
+            for (index, component) in module.path.iter().enumerate() {
+                let is_last = index + 1 == module.path.len();
+                let pop = add_pop(graph, file, &component.text, is_last.then_some(component));
+                let dot = add_pop(graph, file, ".", None);
+                graph.add_edge(parent, pop, 0);
+                graph.add_edge(pop, dot, 0);
+                parent = dot;
+            }
+            let scope = add_scope(graph, file);
+            graph.add_edge(parent, scope, 0);
+            for member in &module.members {
+                let definition = add_pop(graph, file, &member.text, Some(member));
+                graph.add_edge(scope, definition, 0);
+            }
+        }
+
+        file
+    }
+}
+
+/// 識別子として正しい名前か確かめる
+fn name(text: &str, line: usize, column: usize) -> Result<Name, (usize, String)> {
+    let mut chars = text.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+    if !valid {
+        return Err((line + 1, format!("invalid name `{}`", text)));
+    }
+    Ok(Name {
+        text: text.to_string(),
+        line,
+        columns: column..column + text.len(),
+    })
+}
+
+/// pop ノードを追加する。名前が与えられれば、その位置をスパンに持つ定義にする
+fn add_pop(
+    graph: &mut StackGraph,
+    file: Handle<File>,
+    symbol: &str,
+    definition: Option<&Name>,
+) -> Handle<Node> {
+    let id = graph.new_node_id(file);
+    let symbol = graph.add_symbol(symbol);
+    let node = graph
+        .add_pop_symbol_node(id, symbol, definition.is_some())
+        .expect("node IDs are fresh");
+    if let Some(name) = definition {
+        graph.source_info_mut(node).span = Span {
+            start: position(name.line, name.columns.start),
+            end: position(name.line, name.columns.end),
+        };
+    }
+    node
+}
+
+fn add_scope(graph: &mut StackGraph, file: Handle<File>) -> Handle<Node> {
+    let id = graph.new_node_id(file);
+    graph.add_scope_node(id, false).expect("node IDs are fresh")
+}
+
+fn position(line: usize, column: usize) -> Position {
+    Position {
+        line,
+        column: Offset {
+            utf8_offset: column,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+
+/// Set by the Ctrl-C handler; checked by every [`FileCancellation`]
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs a Ctrl-C handler that cancels all running and pending work
+///
+/// Only work checking [`interrupted`] stops after Ctrl-C, so the handler is
+/// installed by commands that do; without it Ctrl-C ends the process.
+pub fn install_interrupt_handler() -> Result<(), Error> {
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst)).map_err(Error::Interrupt)
+}
+
+/// Returns whether Ctrl-C has been pressed
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Cancellation flag for the work on a single file
+///
+/// Cancels when the per-file timeout has elapsed, when the shared `stop` flag is
+/// set (e.g. after another file failed), or when the process was interrupted.
+pub struct FileCancellation<'a> {
+    started: Instant,
+    timeout: Option<Duration>,
+    stop: &'a AtomicBool,
+}
+
+impl<'a> FileCancellation<'a> {
+    /// Starts the clock for a file
+    pub fn new(timeout: Option<Duration>, stop: &'a AtomicBool) -> Self {
+        FileCancellation {
+            started: Instant::now(),
+            timeout,
+            stop,
+        }
+    }
+
+    /// Returns whether the per-file timeout has elapsed
+    pub fn timed_out(&self) -> bool {
+        self.timeout
+            .is_some_and(|timeout| self.started.elapsed() >= timeout)
+    }
+
+    /// Returns whether the work was stopped or interrupted, independent of the timeout
+    pub fn stopped(&self) -> bool {
+        interrupted() || self.stop.load(Ordering::Relaxed)
+    }
+
+    fn cancelled(&self) -> bool {
+        self.stopped() || self.timed_out()
+    }
+}
+
+impl tree_sitter_stack_graphs::CancellationFlag for FileCancellation<'_> {
+    fn check(&self, at: &'static str) -> Result<(), tree_sitter_stack_graphs::CancellationError> {
+        if self.cancelled() {
+            return Err(tree_sitter_stack_graphs::CancellationError(at));
+        }
+        Ok(())
+    }
+}
+
+impl stack_graphs::CancellationFlag for FileCancellation<'_> {
+    fn check(&self, at: &'static str) -> Result<(), stack_graphs::CancellationError> {
+        if self.cancelled() {
+            return Err(stack_graphs::CancellationError(at));
+        }
+        Ok(())
+    }
+}
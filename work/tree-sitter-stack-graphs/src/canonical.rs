@@ -0,0 +1,154 @@
+//! Canonical textual form of stack graphs
+//!
+//! The text lists files, symbols, nodes and edges in a fixed order that does
+//! not depend on the order in which nodes were added to the graph, so it can be
+//! compared across runs and used in snapshot tests:
+//!
+//! ```text
+//! files:
+//!   test.py
+//! symbols:
+//!   x
+//! nodes:
+//!   root: root
+//!   jump_to: jump_to_scope
+//!   test.py#1: scope exported
+//!   test.py#2: pop_symbol `x` definition 1:1-1:2
+//! edges:
+//!   test.py#1 -> test.py#2 (0)
+//! ```
+//!
+//! Nodes are identified by file and local ID; spans are 1-based and omitted for
+//! nodes without a source node.
+
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{Node, NodeID, StackGraph};
+
+/// Returns the kind name of a node, as used in TSG rules
+pub fn node_kind(node: &Node) -> &'static str {
+    match node {
+        Node::Scope(_) => "scope",
+        Node::PushSymbol(_) => "push_symbol",
+        Node::PopSymbol(_) => "pop_symbol",
+        Node::PushScopedSymbol(_) => "push_scoped_symbol",
+        Node::PopScopedSymbol(_) => "pop_scoped_symbol",
+        Node::DropScopes(_) => "drop_scopes",
+        Node::JumpTo(_) => "jump_to_scope",
+        Node::Root(_) => "root",
+    }
+}
+
+/// Converts a StackGraph to its canonical textual form
+///
+/// # Arguments
+/// * `graph` - Reference to the StackGraph to serialize
+///
+/// # Returns
+/// The text described in the module documentation, ending with a newline
+pub fn to_canonical_text(graph: &StackGraph) -> String {
+    let mut text = String::new();
+
+    let mut files = graph
+        .iter_files()
+        .map(|file| graph[file].name().to_string())
+        .collect::<Vec<_>>();
+    files.sort();
+    text.push_str("files:\n");
+    for file in &files {
+        text.push_str(&format!("  {}\n", file));
+    }
+
+    let mut symbols = graph
+        .iter_symbols()
+        .map(|symbol| graph[symbol].to_string())
+        .collect::<Vec<_>>();
+    symbols.sort();
+    text.push_str("symbols:\n");
+    for symbol in &symbols {
+        text.push_str(&format!("  {}\n", symbol));
+    }
+
+    // ファイル名とローカルIDで並べる。ファイルを持たないノードが先頭に来る
+    let mut nodes = graph
+        .iter_nodes()
+        .map(|node| (sort_key(graph, graph[node].id()), node))
+        .collect::<Vec<_>>();
+    nodes.sort();
+    text.push_str("nodes:\n");
+    for (_, node) in &nodes {
+        text.push_str(&format!("  {}\n", node_line(graph, *node)));
+    }
+
+    let mut edges = Vec::new();
+    for (source_key, node) in &nodes {
+        for edge in graph.outgoing_edges(*node) {
+            let sink_key = sort_key(graph, graph[edge.sink].id());
+            edges.push((
+                source_key.clone(),
+                sink_key,
+                edge.precedence,
+                *node,
+                edge.sink,
+            ));
+        }
+    }
+    edges.sort();
+    text.push_str("edges:\n");
+    for (_, _, precedence, source, sink) in edges {
+        text.push_str(&format!(
+            "  {} -> {} ({})\n",
+            node_id(graph, graph[source].id()),
+            node_id(graph, graph[sink].id()),
+            precedence
+        ));
+    }
+
+    text
+}
+
+fn sort_key(graph: &StackGraph, id: NodeID) -> (Option<String>, u32) {
+    (
+        id.file().map(|file| graph[file].name().to_string()),
+        id.local_id(),
+    )
+}
+
+/// `file#local_id` の形でノードを表す。ルートとジャンプ先は名前で表す
+fn node_id(graph: &StackGraph, id: NodeID) -> String {
+    match id.file() {
+        Some(file) => format!("{}#{}", graph[file].name(), id.local_id()),
+        None if id.is_root() => "root".to_string(),
+        None if id.is_jump_to() => "jump_to".to_string(),
+        None => format!("#{}", id.local_id()),
+    }
+}
+
+/// ノード1行分: ID、種類、シンボル、フラグ、スパン
+fn node_line(graph: &StackGraph, node: Handle<Node>) -> String {
+    let data = &graph[node];
+    let mut line = format!("{}: {}", node_id(graph, data.id()), node_kind(data));
+    if let Some(symbol) = data.symbol() {
+        line.push_str(&format!(" `{}`", &graph[symbol]));
+    }
+    if let Node::PushScopedSymbol(push) = data {
+        line.push_str(&format!(" scope={}", node_id(graph, push.scope)));
+    }
+    if data.is_definition() {
+        line.push_str(" definition");
+    }
+    if data.is_reference() {
+        line.push_str(" reference");
+    }
+    if data.is_exported_scope() {
+        line.push_str(" exported");
+    }
+    if let Some(info) = graph.source_info(node) {
+        let span = &info.span;
+        let start = (span.start.line + 1, span.start.column.utf8_offset + 1);
+        let end = (span.end.line + 1, span.end.column.utf8_offset + 1);
+        if start != end {
+            line.push_str(&format!(" {}:{}-{}:{}", start.0, start.1, end.0, end.1));
+        }
+    }
+    line
+}
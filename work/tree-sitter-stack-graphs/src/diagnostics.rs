@@ -0,0 +1,259 @@
+//! Source excerpts and syntax errors for error messages
+//!
+//! An [`Excerpt`] renders one line of a file with the offending columns
+//! underlined, in the style of compiler diagnostics:
+//!
+//! ```text
+//!  --> main.py:3:9
+//!   |
+//! 3 | def f(x:
+//!   |         ^
+//! ```
+//!
+//! [`syntax_errors`] finds the `ERROR` and `MISSING` nodes tree-sitter inserts
+//! for malformed code, and [`blank_syntax_errors`] removes them so that the
+//! well-formed parts of a file can still be built.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// One line of a file with a highlighted range of columns
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Excerpt {
+    pub path: PathBuf,
+    /// 1-based line
+    pub line: usize,
+    /// 1-based column (UTF-8 byte offset within the line)
+    pub column: usize,
+    /// Number of bytes highlighted from `column`, at least 1
+    pub width: usize,
+    /// The text of the line, without the line terminator
+    pub text: String,
+}
+
+impl Excerpt {
+    /// Creates the excerpt of a span in a file
+    ///
+    /// # Arguments
+    /// * `path` - The file, as shown in the excerpt
+    /// * `source` - Contents of the file
+    /// * `start` - 0-based row and byte column where the span starts
+    /// * `end` - 0-based row and byte column where the span ends; spans
+    ///   covering several lines are highlighted up to the end of their first line
+    pub fn new(path: &Path, source: &str, start: (usize, usize), end: (usize, usize)) -> Excerpt {
+        let text = source
+            .lines()
+            .nth(start.0)
+            .unwrap_or_default()
+            .trim_end_matches('\r')
+            .to_string();
+        let end_column = if end.0 == start.0 { end.1 } else { text.len() };
+        Excerpt {
+            path: path.to_path_buf(),
+            line: start.0 + 1,
+            column: start.1 + 1,
+            width: end_column.saturating_sub(start.1).max(1),
+            text,
+        }
+    }
+}
+
+impl fmt::Display for Excerpt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gutter = self.line.to_string().len();
+        // 下線はバイト位置ではなく文字数で揃える
+        let indent = display_width(&self.text, self.column - 1);
+        let width = display_width(
+            &self.text[prefix_len(&self.text, self.column - 1)..],
+            self.width,
+        );
+        writeln!(
+            f,
+            "{:gutter$}--> {}:{}:{}",
+            "",
+            self.path.display(),
+            self.line,
+            self.column
+        )?;
+        writeln!(f, "{:gutter$} |", "")?;
+        writeln!(f, "{} | {}", self.line, self.text)?;
+        write!(
+            f,
+            "{:gutter$} | {:indent$}{}",
+            "",
+            "",
+            "^".repeat(width.max(1))
+        )
+    }
+}
+
+/// 先頭 bytes バイトに収まる文字列の長さ（バイト）を返す
+fn prefix_len(text: &str, bytes: usize) -> usize {
+    let mut len = bytes.min(text.len());
+    while !text.is_char_boundary(len) {
+        len -= 1;
+    }
+    len
+}
+
+/// 先頭 bytes バイトの文字数を返す。行末を越える分は1バイト1文字として数える
+fn display_width(text: &str, bytes: usize) -> usize {
+    let len = prefix_len(text, bytes);
+    text[..len].chars().count() + bytes.saturating_sub(text.len())
+}
+
+/// An `ERROR` or `MISSING` node in a syntax tree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// Kind of the node the parser inserted, for `MISSING` nodes
+    pub missing: Option<String>,
+    pub excerpt: Excerpt,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.missing {
+            Some(kind) => write!(f, "missing `{}`", kind)?,
+            None => write!(f, "syntax error")?,
+        }
+        write!(f, " at {}:{}", self.excerpt.line, self.excerpt.column)
+    }
+}
+
+/// Parses a file and returns its syntax errors
+///
+/// # Arguments
+/// * `grammar` - The tree-sitter grammar of the file's language
+/// * `path` - The file, as shown in the excerpts
+/// * `source` - Contents of the file
+///
+/// # Returns
+/// One error per outermost `ERROR` node and per `MISSING` node, in source
+/// order; empty if the file parses cleanly or cannot be parsed at all
+pub fn syntax_errors(
+    grammar: &tree_sitter::Language,
+    path: &Path,
+    source: &str,
+) -> Vec<SyntaxError> {
+    let mut parser = tree_sitter::Parser::new();
+    if parser.set_language(grammar).is_err() {
+        return Vec::new();
+    }
+    let Some(tree) = parser.parse(source, None) else {
+        return Vec::new();
+    };
+
+    error_nodes(tree.root_node())
+        .into_iter()
+        .map(|node| {
+            let start = node.start_position();
+            let end = node.end_position();
+            SyntaxError {
+                missing: node.is_missing().then(|| node.kind().to_string()),
+                excerpt: Excerpt::new(
+                    path,
+                    source,
+                    (start.row, start.column),
+                    (end.row, end.column),
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Number of times [`blank_syntax_errors`] reparses a file before giving up
+const MAX_BLANKING_ROUNDS: usize = 16;
+
+/// Replaces the source of syntax errors with spaces until the file parses cleanly
+///
+/// `ERROR` nodes are blanked out themselves; for `MISSING` nodes the statement
+/// containing them is, i.e. the smallest enclosing node that spans whole lines.
+/// Blanking may expose new errors, e.g. an indented block whose header was
+/// removed, which are blanked out in the next round. Line breaks are kept, so
+/// the remaining code keeps its line and column positions.
+///
+/// # Returns
+/// The blanked source, or `None` if it still does not parse cleanly after a
+/// bounded number of rounds
+pub fn blank_syntax_errors(grammar: &tree_sitter::Language, source: &str) -> Option<String> {
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(grammar).ok()?;
+
+    let mut bytes = source.as_bytes().to_vec();
+    for _ in 0..MAX_BLANKING_ROUNDS {
+        let tree = parser.parse(&bytes, None)?;
+        let nodes = error_nodes(tree.root_node());
+        if nodes.is_empty() {
+            // ノード境界は文字境界なので、ASCII の空白に置き換えても UTF-8 のまま
+            return String::from_utf8(bytes).ok();
+        }
+
+        let mut changed = false;
+        for node in nodes {
+            let range = if node.is_missing() {
+                statement_range(node, &bytes)
+            } else {
+                node.byte_range()
+            };
+            for byte in &mut bytes[range] {
+                if !matches!(*byte, b' ' | b'\n' | b'\r') {
+                    *byte = b' ';
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return None;
+        }
+    }
+    None
+}
+
+/// ノードを含み、行全体にわたる最小の祖先の範囲を返す。見つからなければ親の範囲
+fn statement_range(node: tree_sitter::Node, source: &[u8]) -> std::ops::Range<usize> {
+    let blank = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .all(|byte| matches!(byte, b' ' | b'\t' | b'\r'))
+    };
+    let mut ancestor = node.parent();
+    while let Some(candidate) = ancestor {
+        if candidate.parent().is_none() {
+            break;
+        }
+        let range = candidate.byte_range();
+        let line_start = source[..range.start]
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |newline| newline + 1);
+        let line_end = source[range.end..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(source.len(), |newline| range.end + newline);
+        if blank(&source[line_start..range.start]) && blank(&source[range.end..line_end]) {
+            return range;
+        }
+        ancestor = candidate.parent();
+    }
+    node.parent()
+        .map_or(node.byte_range(), |parent| parent.byte_range())
+}
+
+/// 最も外側の ERROR ノードと MISSING ノードをソース順に集める
+fn error_nodes(root: tree_sitter::Node) -> Vec<tree_sitter::Node> {
+    let mut nodes = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if node.is_error() || node.is_missing() {
+            nodes.push(node);
+            continue;
+        }
+        if node.has_error() {
+            // 逆順に積んで、ソース順に取り出す
+            let mut cursor = node.walk();
+            let children = node.children(&mut cursor).collect::<Vec<_>>();
+            stack.extend(children.into_iter().rev());
+        }
+    }
+    nodes
+}
@@ -0,0 +1,177 @@
+//! Errors returned by the library API
+//!
+//! [`Error`]'s `Display` is a single line, prefixed with the file the error is
+//! about. [`Error::display_pretty`] renders the error the way the command line
+//! tool prints it, with the offending source lines.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use stack_graphs::storage::StorageError;
+use stack_graphs::CancellationError;
+
+use crate::diagnostics::SyntaxError;
+
+/// Everything that can go wrong while building, indexing or querying stack graphs
+#[derive(Debug)]
+pub enum Error {
+    /// The language configuration is invalid, e.g. an unknown language name or
+    /// a malformed builtins stub; the message names the offending file and line
+    Language(String),
+    /// The tree-sitter grammar of a language could not be loaded
+    Grammar { language: String, message: String },
+    /// The TSG rules of a language failed to compile; `rendered` shows the
+    /// offending rule
+    Compile {
+        path: PathBuf,
+        message: String,
+        rendered: String,
+    },
+    /// Running the TSG rules on a file failed; `rendered` shows the failing
+    /// statement, its stanza and the syntax node the stanza matched
+    Execution {
+        path: PathBuf,
+        message: String,
+        rendered: String,
+    },
+    /// A file has syntax errors
+    Parse {
+        path: PathBuf,
+        errors: Vec<SyntaxError>,
+    },
+    /// A file or directory could not be read or written
+    Io { path: PathBuf, error: io::Error },
+    /// The graph of a file could not be built for another reason
+    Build { path: PathBuf, message: String },
+    /// A file is not in the database
+    NotIndexed(PathBuf),
+    /// Reading or writing the database failed
+    Storage(StorageError),
+    /// Building or path finding was cancelled
+    Cancelled(CancellationError),
+    /// A graph could not be rebuilt from JSON, because the JSON does not follow
+    /// the schema or describes an inconsistent graph
+    Json(String),
+    /// The language server failed to communicate with its client
+    Lsp(String),
+    /// The Ctrl-C handler could not be installed
+    Interrupt(ctrlc::Error),
+}
+
+impl Error {
+    /// Creates an [`Error::Io`] for the given path
+    pub fn io(path: impl Into<PathBuf>, error: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            error,
+        }
+    }
+
+    /// Returns the file the error is about, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Compile { path, .. }
+            | Error::Execution { path, .. }
+            | Error::Parse { path, .. }
+            | Error::Io { path, .. }
+            | Error::Build { path, .. }
+            | Error::NotIndexed(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Returns the message of the error without the file it is about
+    pub fn message(&self) -> String {
+        match self {
+            Error::Language(message) => message.clone(),
+            Error::Grammar { language, message } => {
+                format!("cannot load the {} grammar: {}", language, message)
+            }
+            Error::Compile { message, .. }
+            | Error::Execution { message, .. }
+            | Error::Build { message, .. } => message.clone(),
+            Error::Parse { errors, .. } => match errors.as_slice() {
+                [] => "syntax error".to_string(),
+                [error] => error.to_string(),
+                [error, rest @ ..] => format!("{} and {} more syntax errors", error, rest.len()),
+            },
+            Error::Io { error, .. } => error.to_string(),
+            Error::NotIndexed(_) => "file is not indexed".to_string(),
+            Error::Storage(error) => format!("database error: {}", error),
+            Error::Cancelled(error) => format!("cancelled at {}", error.0),
+            Error::Json(message) => message.clone(),
+            Error::Lsp(message) => format!("language server: {}", message),
+            Error::Interrupt(error) => format!("cannot install the Ctrl-C handler: {}", error),
+        }
+    }
+
+    /// Renders the error as a diagnostic with the offending source lines
+    ///
+    /// The first line reads `FILE: error: MESSAGE`, or `error: MESSAGE` for
+    /// errors not about a single file; the output does not end with a newline.
+    pub fn display_pretty(&self) -> impl fmt::Display + '_ {
+        DisplayPretty(self)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path() {
+            Some(path) => write!(f, "{}: {}", path.display(), self.message()),
+            None => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+struct DisplayPretty<'a>(&'a Error);
+
+impl fmt::Display for DisplayPretty<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            // 上流の整形結果がメッセージと該当箇所を含んでいる
+            Error::Compile { rendered, .. } => write!(f, "error: {}", rendered.trim_end()),
+            Error::Execution { path, rendered, .. } => {
+                write!(f, "{}: error: {}", path.display(), rendered.trim_end())
+            }
+            Error::Parse { path, errors } if !errors.is_empty() => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    writeln!(f, "{}: error: {}", path.display(), error)?;
+                    write!(f, "{}", error.excerpt)?;
+                }
+                Ok(())
+            }
+            error => match error.path() {
+                Some(path) => write!(f, "{}: error: {}", path.display(), error.message()),
+                None => write!(f, "error: {}", error.message()),
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            Error::Storage(error) => Some(error),
+            Error::Cancelled(error) => Some(error),
+            Error::Interrupt(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<StorageError> for Error {
+    fn from(error: StorageError) -> Self {
+        Error::Storage(error)
+    }
+}
+
+impl From<CancellationError> for Error {
+    fn from(error: CancellationError) -> Self {
+        Error::Cancelled(error)
+    }
+}
@@ -0,0 +1,147 @@
+//! DOT backend, for Graphviz
+
+use stack_graphs::graph::StackGraph;
+
+use super::{Backend, ExportEdge, ExportNode, ExportOptions};
+
+/// Converts a StackGraph to DOT format, grouping nodes by file
+///
+/// Nodes are labelled with their kind, local ID, symbol, definition/reference
+/// flags and source span; edges are labelled with their precedence. The nodes of
+/// each file are placed in a `cluster_<file>` subgraph, while the root and
+/// jump-to nodes stay outside of any cluster.
+///
+/// # Arguments
+/// * `graph` - Reference to the StackGraph to visualize
+/// * `options` - Filters selecting the part of the graph to emit
+///
+/// # Returns
+/// A String containing the DOT format representation of the graph
+pub fn to_dot_with_options(graph: &StackGraph, options: &ExportOptions) -> String {
+    super::export(graph, options, DotBackend::default())
+}
+
+/// Writes DOT, with one `cluster_<file>` subgraph per file
+#[derive(Default)]
+pub struct DotBackend {
+    dot: String,
+    in_cluster: bool,
+    overlay: Vec<String>,
+}
+
+impl DotBackend {
+    /// Creates a backend that appends the given statements after the edges
+    ///
+    /// Statements for nodes or edges that were already written add attributes to
+    /// them, which is how overlays such as path-finding traces highlight parts of
+    /// the graph. The output is a `strict digraph`, so that an overlay statement
+    /// for an edge restyles the graph's edge instead of drawing a second one; an
+    /// overlay must therefore have at most one statement per edge.
+    pub fn with_overlay(overlay: Vec<String>) -> Self {
+        DotBackend {
+            overlay,
+            ..Default::default()
+        }
+    }
+}
+
+impl Backend for DotBackend {
+    fn begin(&mut self) {
+        // オーバーレイの辺を元の辺に重ねるため、同じ向きの辺を1本にまとめる
+        if self.overlay.is_empty() {
+            self.dot.push_str("digraph StackGraph {\n");
+        } else {
+            self.dot.push_str("strict digraph StackGraph {\n");
+        }
+
+        // グラフの属性を設定
+        self.dot.push_str("    // Graph attributes\n");
+        self.dot.push_str("    graph [rankdir=LR];\n");
+        self.dot
+            .push_str("    node [shape=box, style=rounded];\n\n");
+
+        self.dot.push_str("    // Nodes\n");
+    }
+
+    fn begin_cluster(&mut self, file: &str) {
+        self.dot
+            .push_str(&format!("\n    subgraph \"cluster_{}\" {{\n", escape(file)));
+        self.dot
+            .push_str(&format!("        label=\"{}\";\n", escape(file)));
+        self.in_cluster = true;
+    }
+
+    fn end_cluster(&mut self) {
+        self.dot.push_str("    }\n");
+        self.in_cluster = false;
+    }
+
+    fn node(&mut self, node: &ExportNode) {
+        let indent = if self.in_cluster { "        " } else { "    " };
+        self.dot.push_str(&format!(
+            "{}\"{}\" [{}];\n",
+            indent,
+            node.id,
+            get_node_attributes(node)
+        ));
+    }
+
+    fn begin_edges(&mut self) {
+        self.dot.push_str("\n    // Edges\n");
+    }
+
+    fn edge(&mut self, edge: &ExportEdge) {
+        // 非表示のスコープを跨ぐエッジは破線にする
+        let attributes = match edge.precedence {
+            Some(precedence) => format!("label=\"{}\"", precedence),
+            None => "style=dashed".to_string(),
+        };
+        self.dot.push_str(&format!(
+            "    \"{}\" -> \"{}\" [{}];\n",
+            edge.source, edge.sink, attributes
+        ));
+    }
+
+    fn finish(mut self) -> String {
+        if !self.overlay.is_empty() {
+            self.dot.push_str("\n    // Overlay\n");
+            for statement in &self.overlay {
+                self.dot.push_str(&format!("    {}\n", statement));
+            }
+        }
+        self.dot.push_str("}\n");
+        self.dot
+    }
+}
+
+/// ノードの属性を取得
+fn get_node_attributes(node: &ExportNode) -> String {
+    let mut attrs = Vec::new();
+
+    // ノードの種類に応じて色を設定
+    attrs.push(format!("fillcolor=\"{}\"", node.colour));
+    attrs.push("style=\"filled\"".to_string());
+
+    // 定義ノードは太枠、参照ノードは二重枠で区別する
+    if node.definition {
+        attrs.push("penwidth=2".to_string());
+    } else if node.reference {
+        attrs.push("peripheries=2".to_string());
+    }
+
+    // ノード情報を追加
+    let label = node
+        .label
+        .iter()
+        .map(|line| escape(line))
+        .collect::<Vec<_>>()
+        .join("\\n");
+    attrs.push(format!("label=\"{}\"", label));
+
+    attrs.join(", ")
+}
+
+/// Escapes text for use in a DOT string literal
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,47 @@
+//! HTML backend, a self-contained viewer for browsers
+
+use std::collections::BTreeMap;
+
+use stack_graphs::graph::StackGraph;
+
+use crate::json_export;
+
+/// Viewer page; the placeholders are replaced by the graph and the colours
+const VIEWER_TEMPLATE: &str = include_str!("viewer.html");
+
+/// Converts a StackGraph to a single HTML file that displays it
+///
+/// The page embeds the graph in the JSON schema of [`json_export`] together with
+/// the fill colour of each node kind from [`node_colour`](super::node_colour),
+/// and a small script that lays the nodes out by file, supports panning and
+/// zooming, shows the symbol, span and edges of a clicked node and searches
+/// nodes by symbol. It needs no network access or other files.
+///
+/// # Arguments
+/// * `graph` - Reference to the StackGraph to visualize
+///
+/// # Returns
+/// A String containing the HTML document
+pub fn to_html(graph: &StackGraph) -> String {
+    let colours = graph
+        .iter_nodes()
+        .map(|node| {
+            (
+                super::node_kind(&graph[node]),
+                super::node_colour(&graph[node]),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+    let graph_json = serde_json::to_string(&json_export::to_graph_json(graph))
+        .expect("graph JSON is serializable");
+    let colours_json = serde_json::to_string(&colours).expect("colours are serializable");
+
+    VIEWER_TEMPLATE
+        .replace("/*KIND_COLOURS*/", &script_safe(&colours_json))
+        .replace("/*GRAPH_JSON*/", &script_safe(&graph_json))
+}
+
+/// `</script>` などで script 要素が閉じられないようにする
+fn script_safe(json: &str) -> String {
+    json.replace("</", "<\\/")
+}
@@ -0,0 +1,108 @@
+//! Mermaid backend, for flowcharts embedded in Markdown
+
+use stack_graphs::graph::StackGraph;
+
+use super::{Backend, ExportEdge, ExportNode, ExportOptions};
+
+/// Converts a StackGraph to a Mermaid flowchart
+///
+/// Nodes carry the same labels and fill colours as in DOT; definitions get a
+/// thick border and references the double-bordered subroutine shape. The nodes of
+/// each file are placed in a subgraph titled with the file name.
+///
+/// # Arguments
+/// * `graph` - Reference to the StackGraph to visualize
+/// * `options` - Filters selecting the part of the graph to emit
+///
+/// # Returns
+/// A String containing the Mermaid flowchart
+pub fn to_mermaid(graph: &StackGraph, options: &ExportOptions) -> String {
+    super::export(graph, options, MermaidBackend::default())
+}
+
+/// Writes a Mermaid `flowchart`, with one subgraph per file
+#[derive(Default)]
+pub struct MermaidBackend {
+    chart: String,
+    styles: Vec<String>,
+    clusters: usize,
+    in_cluster: bool,
+}
+
+impl Backend for MermaidBackend {
+    fn begin(&mut self) {
+        self.chart.push_str("flowchart LR\n");
+    }
+
+    fn begin_cluster(&mut self, file: &str) {
+        // ファイル名は識別子に使えないので連番の ID を振る
+        self.chart.push_str(&format!(
+            "    subgraph file{} [\"{}\"]\n",
+            self.clusters,
+            escape(file)
+        ));
+        self.clusters += 1;
+        self.in_cluster = true;
+    }
+
+    fn end_cluster(&mut self) {
+        self.chart.push_str("    end\n");
+        self.in_cluster = false;
+    }
+
+    fn node(&mut self, node: &ExportNode) {
+        let indent = if self.in_cluster { "        " } else { "    " };
+        let label = node
+            .label
+            .iter()
+            .map(|line| escape(line))
+            .collect::<Vec<_>>()
+            .join("<br/>");
+        let (open, close) = if node.reference {
+            ("[[", "]]")
+        } else {
+            ("[", "]")
+        };
+        self.chart.push_str(&format!(
+            "{}{}{}\"{}\"{}\n",
+            indent, node.id, open, label, close
+        ));
+
+        // スタイルはノードの後にまとめて出力する
+        let mut style = format!("style {} fill:{}", node.id, node.colour);
+        if node.definition {
+            style.push_str(",stroke-width:3px");
+        }
+        self.styles.push(style);
+    }
+
+    fn begin_edges(&mut self) {}
+
+    fn edge(&mut self, edge: &ExportEdge) {
+        match edge.precedence {
+            Some(precedence) => self.chart.push_str(&format!(
+                "    {} -->|{}| {}\n",
+                edge.source, precedence, edge.sink
+            )),
+            None => self
+                .chart
+                .push_str(&format!("    {} -.-> {}\n", edge.source, edge.sink)),
+        }
+    }
+
+    fn finish(mut self) -> String {
+        for style in &self.styles {
+            self.chart.push_str(&format!("    {}\n", style));
+        }
+        self.chart
+    }
+}
+
+/// Mermaid のラベル用に特殊文字を実体参照に置き換える。`<` などは HTML として解釈されるため置き換える
+fn escape(text: &str) -> String {
+    text.replace('&', "#amp;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+        .replace('"', "#quot;")
+        .replace('`', "#96;")
+}
@@ -0,0 +1,411 @@
+//! Export of stack graphs to graph description languages
+//!
+//! [`export`] walks the graph once, applying the [`ExportOptions`] filters and
+//! grouping nodes by file, and hands every node and edge to a [`Backend`] that
+//! writes one format. Backends exist for DOT ([`dot`]), Mermaid flowcharts
+//! ([`mermaid`]) and GraphML ([`graphml`]); all of them colour nodes by kind
+//! with [`node_colour`]. The [`html`] viewer embeds the whole graph as JSON
+//! instead and lays it out in the browser.
+//!
+//! [`Exporter`] renders a graph in any [`ExportFormat`], including the JSON
+//! and canonical text forms.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{Node, StackGraph};
+
+use crate::canonical;
+pub use crate::canonical::node_kind;
+use crate::json_export;
+use crate::query::{self, Location};
+
+pub mod dot;
+pub mod graphml;
+pub mod html;
+pub mod mermaid;
+
+/// Options restricting which part of the graph an [`Exporter`] emits
+#[derive(Clone, Default)]
+pub struct ExportOptions {
+    /// Only include nodes of these files (all files if empty)
+    pub files: Vec<String>,
+    /// Hide plain scope nodes; edges through them are drawn dashed between the
+    /// visible nodes on either side
+    pub hide_scopes: bool,
+    /// Only include nodes within `depth` hops of this node, following edges in
+    /// either direction
+    pub focus: Option<Handle<Node>>,
+    /// Number of hops around `focus`
+    pub depth: usize,
+}
+
+/// Output formats of [`Exporter::render`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Dot,
+    Mermaid,
+    Graphml,
+    Html,
+    Json,
+    Text,
+}
+
+impl ExportFormat {
+    /// All formats, in the order they are listed in help texts
+    pub const ALL: [ExportFormat; 6] = [
+        ExportFormat::Dot,
+        ExportFormat::Mermaid,
+        ExportFormat::Graphml,
+        ExportFormat::Html,
+        ExportFormat::Json,
+        ExportFormat::Text,
+    ];
+
+    /// Returns the lowercase name of the format, as accepted by [`FromStr`]
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Dot => "dot",
+            ExportFormat::Mermaid => "mermaid",
+            ExportFormat::Graphml => "graphml",
+            ExportFormat::Html => "html",
+            ExportFormat::Json => "json",
+            ExportFormat::Text => "text",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ExportFormat::ALL
+            .into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| {
+                let names = ExportFormat::ALL.map(ExportFormat::name);
+                format!(
+                    "unknown format `{}`, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Renders a graph, or the part of it selected by [`ExportOptions`]
+///
+/// ```no_run
+/// # let graph = stack_graphs::graph::StackGraph::new();
+/// use stack_graph_tools::{ExportFormat, Exporter};
+///
+/// let dot = Exporter::new(&graph).render(ExportFormat::Dot);
+/// ```
+pub struct Exporter<'a> {
+    graph: &'a StackGraph,
+    options: ExportOptions,
+}
+
+impl<'a> Exporter<'a> {
+    /// Creates an exporter for the whole graph
+    pub fn new(graph: &'a StackGraph) -> Self {
+        Exporter {
+            graph,
+            options: ExportOptions::default(),
+        }
+    }
+
+    /// Sets the filters applied by the DOT, Mermaid and GraphML formats
+    pub fn with_options(mut self, options: ExportOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Renders the graph in the given format
+    ///
+    /// JSON, HTML and the canonical text always contain the whole graph; the
+    /// JSON output ends with a newline like the others.
+    pub fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Dot => self.to_dot(),
+            ExportFormat::Mermaid => self.to_mermaid(),
+            ExportFormat::Graphml => self.to_graphml(),
+            ExportFormat::Html => self.to_html(),
+            ExportFormat::Json => self.to_json() + "\n",
+            ExportFormat::Text => self.to_text(),
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        dot::to_dot_with_options(self.graph, &self.options)
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        mermaid::to_mermaid(self.graph, &self.options)
+    }
+
+    pub fn to_graphml(&self) -> String {
+        graphml::to_graphml(self.graph, &self.options)
+    }
+
+    pub fn to_html(&self) -> String {
+        html::to_html(self.graph)
+    }
+
+    /// Returns the pretty-printed JSON of the whole graph, without a trailing newline
+    pub fn to_json(&self) -> String {
+        json_export::to_json(self.graph)
+    }
+
+    /// Returns the canonical text, which does not depend on the order nodes were added in
+    pub fn to_text(&self) -> String {
+        canonical::to_canonical_text(self.graph)
+    }
+}
+
+/// A node as seen by a [`Backend`]
+pub struct ExportNode {
+    /// Identifier that is unique within the graph, e.g. `n3`
+    pub id: String,
+    /// File of the node, `None` for the root and jump-to nodes
+    pub file: Option<String>,
+    /// Kind name as returned by [`node_kind`]
+    pub kind: &'static str,
+    /// Fill colour as returned by [`node_colour`]
+    pub colour: &'static str,
+    /// Label lines: kind, local ID and symbol; flags; span or file name
+    pub label: Vec<String>,
+    pub definition: bool,
+    pub reference: bool,
+}
+
+/// An edge as seen by a [`Backend`]
+pub struct ExportEdge {
+    pub source: String,
+    pub sink: String,
+    /// Precedence of the edge, `None` for edges bridging hidden scope nodes
+    pub precedence: Option<i32>,
+}
+
+/// Writes the nodes and edges handed over by [`export`] in one output format
+///
+/// Nodes without a file come first, then the nodes of each file between
+/// [`begin_cluster`](Backend::begin_cluster) and
+/// [`end_cluster`](Backend::end_cluster), then all edges.
+pub trait Backend {
+    /// Called before the first node
+    fn begin(&mut self);
+    /// Called before the nodes of a file
+    fn begin_cluster(&mut self, file: &str);
+    /// Called after the nodes of a file
+    fn end_cluster(&mut self);
+    fn node(&mut self, node: &ExportNode);
+    /// Called after the last node and before the first edge
+    fn begin_edges(&mut self);
+    fn edge(&mut self, edge: &ExportEdge);
+    /// Returns the complete output
+    fn finish(self) -> String;
+}
+
+/// Exports the part of a graph selected by `options` with the given backend
+///
+/// # Arguments
+/// * `graph` - Reference to the StackGraph to export
+/// * `options` - Filters selecting the part of the graph to emit
+/// * `backend` - Writer of the output format
+///
+/// # Returns
+/// The output produced by the backend
+pub fn export<B: Backend>(graph: &StackGraph, options: &ExportOptions, mut backend: B) -> String {
+    let visible = visible_nodes(graph, options);
+    backend.begin();
+
+    // ファイルを持たないノードを先に、残りをファイルごとにまとめて渡す
+    let mut clusters: BTreeMap<&str, Vec<Handle<Node>>> = BTreeMap::new();
+    for node in graph.iter_nodes().filter(|node| visible.contains(node)) {
+        match graph[node].id().file() {
+            Some(file) => clusters.entry(graph[file].name()).or_default().push(node),
+            None => backend.node(&export_node(graph, node)),
+        }
+    }
+    for (file, nodes) in &clusters {
+        backend.begin_cluster(file);
+        for &node in nodes {
+            backend.node(&export_node(graph, node));
+        }
+        backend.end_cluster();
+    }
+
+    backend.begin_edges();
+    for node in graph.iter_nodes().filter(|node| visible.contains(node)) {
+        for edge in graph.outgoing_edges(node) {
+            if visible.contains(&edge.sink) {
+                backend.edge(&ExportEdge {
+                    source: node_id(node),
+                    sink: node_id(edge.sink),
+                    precedence: Some(edge.precedence),
+                });
+            } else if options.hide_scopes && is_hidden_scope(graph, edge.sink) {
+                // 非表示のスコープを経由して到達する表示ノードを結ぶ
+                for sink in visible_sinks_through(graph, edge.sink, &visible) {
+                    backend.edge(&ExportEdge {
+                        source: node_id(node),
+                        sink: node_id(sink),
+                        precedence: None,
+                    });
+                }
+            }
+        }
+    }
+
+    backend.finish()
+}
+
+/// Returns the fill colour of a node, based on its kind
+pub fn node_colour(node: &Node) -> &'static str {
+    match node {
+        Node::Scope(_) => "lightblue",
+        Node::PushSymbol(_) => "lightgreen",
+        Node::PopSymbol(_) => "lightpink",
+        Node::PushScopedSymbol(_) => "lightgreen",
+        Node::PopScopedSymbol(_) => "lightpink",
+        Node::DropScopes(_) => "yellow",
+        Node::JumpTo(_) => "orange",
+        Node::Root(_) => "purple",
+    }
+}
+
+/// Returns the identifier of a node in the exported graphs, e.g. `n3`
+pub fn node_id(node: Handle<Node>) -> String {
+    format!("n{}", node.as_usize())
+}
+
+fn export_node(graph: &StackGraph, node: Handle<Node>) -> ExportNode {
+    let data = &graph[node];
+    ExportNode {
+        id: node_id(node),
+        file: data.id().file().map(|file| graph[file].name().to_string()),
+        kind: node_kind(data),
+        colour: node_colour(data),
+        label: node_label(graph, node),
+        definition: data.is_definition(),
+        reference: data.is_reference(),
+    }
+}
+
+/// ラベルの各行を組み立てる
+fn node_label(graph: &StackGraph, node: Handle<Node>) -> Vec<String> {
+    let data = &graph[node];
+    let mut lines = Vec::new();
+
+    let mut header = format!("{} [{}]", node_kind(data), data.id().local_id());
+    if let Some(symbol) = query::node_symbol(graph, node) {
+        header.push_str(&format!(" `{}`", symbol));
+    }
+    lines.push(header);
+
+    let mut flags = Vec::new();
+    if data.is_definition() {
+        flags.push("definition");
+    }
+    if data.is_reference() {
+        flags.push("reference");
+    }
+    if data.is_exported_scope() {
+        flags.push("exported");
+    }
+    if !flags.is_empty() {
+        lines.push(flags.join(", "));
+    }
+
+    match Location::of_node(graph, node) {
+        Some(location) if !location.is_empty() => lines.push(location.to_string()),
+        _ => {
+            if let Some(file) = data.id().file() {
+                lines.push(graph[file].name().to_string());
+            }
+        }
+    }
+
+    lines
+}
+
+/// オプションに従って出力するノードを選ぶ
+fn visible_nodes(graph: &StackGraph, options: &ExportOptions) -> HashSet<Handle<Node>> {
+    let in_files = |node: Handle<Node>| match graph[node].id().file() {
+        Some(file) => {
+            options.files.is_empty() || options.files.iter().any(|f| f == graph[file].name())
+        }
+        None => true,
+    };
+    let mut visible = graph
+        .iter_nodes()
+        .filter(|&node| in_files(node))
+        .filter(|&node| !(options.hide_scopes && is_hidden_scope(graph, node)))
+        .collect::<HashSet<_>>();
+
+    if let Some(focus) = options.focus {
+        let neighbourhood = neighbourhood(graph, focus, options.depth);
+        visible.retain(|node| neighbourhood.contains(node));
+    }
+    visible
+}
+
+fn is_hidden_scope(graph: &StackGraph, node: Handle<Node>) -> bool {
+    matches!(graph[node], Node::Scope(_))
+}
+
+/// 向きを無視して depth ホップ以内にあるノードを集める
+fn neighbourhood(graph: &StackGraph, focus: Handle<Node>, depth: usize) -> HashSet<Handle<Node>> {
+    let mut adjacent: HashMap<Handle<Node>, Vec<Handle<Node>>> = HashMap::new();
+    for node in graph.iter_nodes() {
+        for edge in graph.outgoing_edges(node) {
+            adjacent.entry(node).or_default().push(edge.sink);
+            adjacent.entry(edge.sink).or_default().push(node);
+        }
+    }
+
+    let mut seen = HashSet::from([focus]);
+    let mut frontier = vec![focus];
+    for _ in 0..depth {
+        let mut next = Vec::new();
+        for node in frontier {
+            for &neighbour in adjacent.get(&node).into_iter().flatten() {
+                if seen.insert(neighbour) {
+                    next.push(neighbour);
+                }
+            }
+        }
+        frontier = next;
+    }
+    seen
+}
+
+/// 非表示ノードだけを通って到達できる表示ノードを返す
+fn visible_sinks_through(
+    graph: &StackGraph,
+    hidden: Handle<Node>,
+    visible: &HashSet<Handle<Node>>,
+) -> BTreeSet<Handle<Node>> {
+    let mut sinks = BTreeSet::new();
+    let mut seen = HashSet::from([hidden]);
+    let mut stack = vec![hidden];
+    while let Some(node) = stack.pop() {
+        for edge in graph.outgoing_edges(node) {
+            if visible.contains(&edge.sink) {
+                sinks.insert(edge.sink);
+            } else if is_hidden_scope(graph, edge.sink) && seen.insert(edge.sink) {
+                stack.push(edge.sink);
+            }
+        }
+    }
+    sinks
+}
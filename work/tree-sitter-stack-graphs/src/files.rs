@@ -0,0 +1,106 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::Error;
+
+/// Collects the source files reachable from the given paths
+///
+/// # Arguments
+/// * `paths` - Files or directories given on the command line
+/// * `extensions` - Extensions, without the leading dot, of the files picked up
+///   when walking directories
+///
+/// # Returns
+/// The sorted, de-duplicated list of files, normalized with [`normalize`] so
+/// that `a.py` and `./a.py` are the same file. Explicitly named files are kept
+/// regardless of their extension; directories are walked recursively for files
+/// with one of the extensions, skipping hidden directories such as `.git` or
+/// `.venv`.
+pub fn collect_source_files(paths: &[PathBuf], extensions: &[&str]) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for path in paths {
+        let metadata = fs::metadata(path).map_err(|err| Error::io(path, err))?;
+        if metadata.is_dir() {
+            walk_dir(path, extensions, &mut files)?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+    let mut files: Vec<PathBuf> = files.iter().map(|file| normalize(file)).collect();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Normalizes a path by dropping its `.` components
+///
+/// Files are named in the graph by their normalized path, so positions and
+/// project roots have to be normalized the same way before they are compared
+/// with them. `..` components are kept, as resolving them would need the file
+/// system.
+///
+/// # Returns
+/// The path without `.` components, which is empty for `.` itself
+pub fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+/// ディレクトリを再帰的に走査して、拡張子が一致するファイルを集める
+fn walk_dir(dir: &Path, extensions: &[&str], files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).map_err(|err| Error::io(dir, err))? {
+        let path = entry.map_err(|err| Error::io(dir, err))?.path();
+        if path.is_dir() {
+            if !is_hidden(&path) {
+                walk_dir(&path, extensions, files)?;
+            }
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| extensions.contains(&ext))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+/// Returns the project root a file belongs to
+///
+/// Module paths are computed relative to this directory, so that `pkg/util.py`
+/// below a directory given on the command line is the module `pkg.util`.
+///
+/// # Arguments
+/// * `roots` - Files or directories given on the command line
+/// * `file` - A file collected from them
+///
+/// # Returns
+/// The outermost of the given directories containing the file, or the file's
+/// own directory if it was only named explicitly
+pub fn source_root(roots: &[PathBuf], file: &Path) -> PathBuf {
+    let file = normalize(file);
+    roots
+        .iter()
+        .map(|root| normalize(root))
+        .filter(|root| file.starts_with(root) && file != root.as_path())
+        .min_by_key(|root| root.components().count())
+        .unwrap_or_else(|| file.parent().unwrap_or(Path::new("")).to_path_buf())
+}
+
+/// Returns the path of a file relative to its project root, with `/` separators
+pub fn relative_path(root: &Path, file: &Path) -> String {
+    let (root, file) = (normalize(root), normalize(file));
+    let relative = file.strip_prefix(&root).unwrap_or(&file);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,613 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{File, StackGraph};
+use stack_graphs::partial::{PartialPath, PartialPaths};
+use stack_graphs::stitching::{ForwardPartialPathStitcher, StitcherConfig};
+use stack_graphs::storage::{FileStatus, SQLiteReader, SQLiteWriter};
+use stack_graphs::{CancellationError, CancellationFlag, NoCancellation};
+
+use crate::analyze::{self, FileOutcome, FileReport};
+use crate::cancel::FileCancellation;
+use crate::diagnostics::SyntaxError;
+use crate::error::Error;
+use crate::files;
+use crate::query::{self, Location, Position, Resolution};
+use crate::rules::{Language, LanguageRegistry};
+use crate::unresolved::UnresolvedReport;
+
+/// A SQLite database holding per-file stack graphs and partial paths
+///
+/// Files are added with [`Indexer::index`](crate::Indexer::index); the methods
+/// here read and maintain the database without building any graph.
+pub struct Index {
+    path: PathBuf,
+}
+
+impl Index {
+    /// Database file used when no `--database` option is given
+    pub const DEFAULT_PATH: &'static str = ".stack-graphs.sqlite";
+
+    /// Refers to the database at a path; it is created when first written to
+    pub fn new(path: impl Into<PathBuf>) -> Index {
+        Index { path: path.into() }
+    }
+
+    /// Returns the path of the database file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Lists the index entries below the given paths, or all entries if none are given
+    pub fn status(&self, paths: &[PathBuf]) -> Result<Vec<IndexEntry>, Error> {
+        status(&self.path, paths)
+    }
+
+    /// Removes the index entries below the given paths, or the whole index if none are given
+    ///
+    /// # Returns
+    /// The number of removed file entries
+    pub fn clean(&self, paths: &[PathBuf]) -> Result<usize, Error> {
+        clean(&self.path, paths)
+    }
+
+    /// Resolves the reference at a position using only the indexed data
+    ///
+    /// # Returns
+    /// `None` if there is no reference at the position
+    pub fn resolve(&self, position: &Position) -> Result<Option<Resolution>, Error> {
+        resolve(&self.path, position)
+    }
+
+    /// Checks the references of indexed files using only the indexed data
+    ///
+    /// # Arguments
+    /// * `paths` - Files or directories in the database, or all files if empty
+    pub fn unresolved(&self, paths: &[PathBuf]) -> Result<UnresolvedReport, Error> {
+        unresolved(&self.path, paths)
+    }
+}
+
+/// Computes the content hash stored as the tag of a builtins stub's index entry
+pub fn content_hash(source: &str) -> String {
+    format!("{:x}", Sha256::digest(source.as_bytes()))
+}
+
+/// Computes the tag stored with a source file's index entry
+///
+/// Besides the file's contents, the tag covers everything else its graph is
+/// built from, so that a file is rebuilt when any of it changes.
+///
+/// # Arguments
+/// * `language` - The file's language, whose rules and builtins stub are hashed;
+///   `None` for files of unknown languages
+/// * `source` - Contents of the file
+/// * `tolerant` - Whether the file is built in tolerant mode
+/// * `relative_path` - Path of the file relative to its project root, from which
+///   the rules derive its module path
+pub fn file_tag(
+    language: Option<&Language>,
+    source: &str,
+    tolerant: bool,
+    relative_path: &str,
+) -> String {
+    let rules = language.map_or("", |language| language.rules.as_str());
+    let stub = language
+        .and_then(|language| language.builtins.as_ref())
+        .map_or("", |stub| stub.source());
+    let tolerant = if tolerant { "tolerant" } else { "strict" };
+    let mut hasher = Sha256::new();
+    // 区切りが曖昧にならないよう、各部分の前に長さを入れる
+    for part in [source, rules, stub, tolerant, relative_path] {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Number of files built in parallel before their results are written
+const WRITE_BATCH_SIZE: usize = 64;
+
+/// What indexing did to the files below the indexed paths
+#[derive(Default)]
+pub struct IndexSummary {
+    /// Files that had no entry before
+    pub added: Vec<PathBuf>,
+    /// Files whose tag differed from the stored one, see [`file_tag`]
+    pub updated: Vec<PathBuf>,
+    /// Files whose tag matched, which were skipped
+    pub unchanged: Vec<PathBuf>,
+    /// Entries of files that no longer exist
+    pub removed: Vec<PathBuf>,
+    /// Files that could not be read or built, or that timed out
+    pub failed: Vec<FileReport>,
+    /// Files with syntax errors that were built from their well-formed parts in
+    /// tolerant mode; they are also listed as added or updated
+    pub partial: Vec<FileReport>,
+}
+
+/// Options for [`Indexer::index`](crate::Indexer::index)
+#[derive(Clone, Copy, Default)]
+pub struct IndexOptions {
+    /// Continue with the remaining files after a failure
+    pub keep_going: bool,
+    /// Rebuild files even if their tag is unchanged
+    pub force: bool,
+    /// Give up on a file after this long
+    pub timeout: Option<Duration>,
+    /// Index the well-formed parts of files with syntax errors instead of
+    /// failing them, see [`BuildOptions::tolerant`](crate::BuildOptions::tolerant)
+    pub tolerant: bool,
+}
+
+/// Brings the index up to date with the given files
+///
+/// A file is rebuilt only if its [`file_tag`] differs from the tag stored in the
+/// database, unless `force` is set. Entries below `roots` whose files no longer
+/// exist are removed. The languages' builtins stubs are stored as files named
+/// after the stubs, which are not part of the summary. Graphs and partial paths are computed in parallel, in
+/// batches whose results are written to the database by the calling thread.
+///
+/// # Arguments
+/// * `languages` - The languages whose TSG rules are run, chosen per file by
+///   its extension
+/// * `database` - Path of the SQLite database, created if missing
+/// * `roots` - The files and directories the files were collected from
+/// * `files` - Source files to index
+/// * `options` - Failure handling, forced re-indexing, per-file timeout and
+///   tolerance of syntax errors
+pub fn index_files(
+    languages: &LanguageRegistry,
+    database: &Path,
+    roots: &[PathBuf],
+    files: &[PathBuf],
+    options: IndexOptions,
+) -> Result<IndexSummary, Error> {
+    let mut writer = SQLiteWriter::open(database)?;
+    let mut summary = IndexSummary::default();
+
+    // 既存のエントリをパスごとに読み込んでおく
+    let mut stored = status(database, roots)?
+        .into_iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect::<HashMap<_, _>>();
+
+    // 内容が変わったファイルだけを構築対象にする
+    let mut pending = Vec::new();
+    for path in files {
+        let previous = stored.remove(path);
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                summary.failed.push(FileReport {
+                    path: path.clone(),
+                    outcome: FileOutcome::Failed {
+                        error: Error::io(path, err),
+                    },
+                });
+                if options.keep_going {
+                    continue;
+                }
+                break;
+            }
+        };
+
+        let root = files::source_root(roots, path);
+        let tag = file_tag(
+            languages.for_path(path),
+            &source,
+            options.tolerant,
+            &files::relative_path(&root, path),
+        );
+        let existed = match previous {
+            Some(entry) if entry.tag == tag && !options.force => {
+                // 前回の失敗はそのまま報告する
+                if let FileStatus::Error(message) = entry.status {
+                    summary.failed.push(FileReport {
+                        path: path.clone(),
+                        outcome: FileOutcome::Failed {
+                            error: Error::Build {
+                                path: path.clone(),
+                                message,
+                            },
+                        },
+                    });
+                } else {
+                    summary.unchanged.push(path.clone());
+                }
+                continue;
+            }
+            previous => previous.is_some(),
+        };
+        pending.push(PendingFile {
+            path: path.clone(),
+            root,
+            source,
+            tag,
+            existed,
+        });
+    }
+
+    index_builtins(database, &mut writer, languages, options.force)?;
+
+    let stop = AtomicBool::new(false);
+    for batch in pending.chunks(WRITE_BATCH_SIZE) {
+        if !options.keep_going && !summary.failed.is_empty() {
+            break;
+        }
+        let prepared = batch
+            .par_iter()
+            .map(|file| {
+                let cancellation = FileCancellation::new(options.timeout, &stop);
+                let result = prepare_file(languages, file, options.tolerant, &cancellation);
+                if !options.keep_going
+                    && matches!(result, PreparedFile::Failed(_) | PreparedFile::TimedOut)
+                {
+                    stop.store(true, Ordering::Relaxed);
+                }
+                result
+            })
+            .collect::<Vec<_>>();
+
+        for (file, result) in batch.iter().zip(prepared) {
+            let outcome = write_file(&mut writer, file, result)?;
+            let Some(outcome) = outcome else {
+                continue;
+            };
+            if file.existed {
+                summary.updated.push(file.path.clone());
+            } else {
+                summary.added.push(file.path.clone());
+            }
+            let report = FileReport {
+                path: file.path.clone(),
+                outcome,
+            };
+            match &report.outcome {
+                outcome if outcome.is_failure() => summary.failed.push(report),
+                FileOutcome::Built { syntax_errors, .. } if !syntax_errors.is_empty() => {
+                    summary.partial.push(report)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // 削除されたファイルのエントリを取り除く
+    for path in stored.into_keys() {
+        if !path.exists() {
+            writer.clean_file(&path)?;
+            summary.removed.push(path);
+        }
+    }
+    summary.removed.sort();
+
+    Ok(summary)
+}
+
+/// 構築待ちのファイル
+struct PendingFile {
+    path: PathBuf,
+    /// モジュールパスの基準となるプロジェクトルート
+    root: PathBuf,
+    source: String,
+    tag: String,
+    existed: bool,
+}
+
+/// 並列に計算した1ファイル分のグラフと部分パス
+enum PreparedFile {
+    Built {
+        // 他の結果と大きさを揃えるため箱に入れる
+        graph: Box<StackGraph>,
+        file: Handle<File>,
+        partials: PartialPaths,
+        paths: Vec<PartialPath>,
+        syntax_errors: Vec<SyntaxError>,
+    },
+    Failed(Error),
+    TimedOut,
+    Cancelled,
+}
+
+/// グラフとファイル内で完結する最小の部分パス集合を計算する
+fn prepare_file(
+    languages: &LanguageRegistry,
+    pending: &PendingFile,
+    tolerant: bool,
+    cancellation: &FileCancellation,
+) -> PreparedFile {
+    if cancellation.stopped() {
+        return PreparedFile::Cancelled;
+    }
+    let Some(language) = languages.for_path(&pending.path) else {
+        return PreparedFile::Failed(Error::Build {
+            path: pending.path.clone(),
+            message: analyze::UNKNOWN_LANGUAGE.to_string(),
+        });
+    };
+
+    let mut graph = StackGraph::new();
+    let (path, root, source) = (&pending.path, &pending.root, &pending.source);
+    let result = if tolerant {
+        analyze::build_source_tolerant(language, &mut graph, path, root, source, cancellation)
+    } else {
+        analyze::build_source(language, &mut graph, path, root, source, cancellation)
+            .map(|file| (file, Vec::new()))
+    };
+    let (file, syntax_errors) = match result {
+        Ok(built) => built,
+        Err(Error::Cancelled(_)) if cancellation.timed_out() => return PreparedFile::TimedOut,
+        Err(Error::Cancelled(_)) => return PreparedFile::Cancelled,
+        Err(err) => return PreparedFile::Failed(err),
+    };
+
+    match minimal_partial_paths(&graph, file, cancellation) {
+        Ok((partials, paths)) => PreparedFile::Built {
+            graph: Box::new(graph),
+            file,
+            partials,
+            paths,
+            syntax_errors,
+        },
+        Err(_) if cancellation.timed_out() => PreparedFile::TimedOut,
+        Err(_) => PreparedFile::Cancelled,
+    }
+}
+
+/// ファイル内で完結する最小の部分パス集合を計算する
+fn minimal_partial_paths(
+    graph: &StackGraph,
+    file: Handle<File>,
+    cancellation: &dyn CancellationFlag,
+) -> Result<(PartialPaths, Vec<PartialPath>), CancellationError> {
+    let mut partials = PartialPaths::new();
+    let mut paths: Vec<PartialPath> = Vec::new();
+    ForwardPartialPathStitcher::find_minimal_partial_path_set_in_file(
+        graph,
+        &mut partials,
+        file,
+        StitcherConfig::default(),
+        cancellation,
+        |_graph, _partials, path| paths.push(path.clone()),
+    )?;
+    Ok((partials, paths))
+}
+
+/// 組み込み名のスタブを疑似ファイルとして保存する。内容が変わっていなければ何もしない
+fn index_builtins(
+    database: &Path,
+    writer: &mut SQLiteWriter,
+    languages: &LanguageRegistry,
+    force: bool,
+) -> Result<(), Error> {
+    let mut reader = SQLiteReader::open(database)?;
+    for stub in languages.builtins() {
+        let tag = content_hash(stub.source());
+        let status = reader.status_for_file(stub.file_name(), Some(tag.as_str()))?;
+        if matches!(status, FileStatus::Indexed) && !force {
+            continue;
+        }
+
+        let mut graph = StackGraph::new();
+        let file = stub.add_to_graph(&mut graph);
+        let (mut partials, paths) = minimal_partial_paths(&graph, file, &NoCancellation)?;
+        writer.clean_file(Path::new(stub.file_name()))?;
+        writer.store_result_for_file(&graph, file, &tag, &mut partials, &paths)?;
+    }
+    Ok(())
+}
+
+/// 計算結果をデータベースに書き込む。中断されたファイルは何も書かない
+fn write_file(
+    writer: &mut SQLiteWriter,
+    pending: &PendingFile,
+    prepared: PreparedFile,
+) -> Result<Option<FileOutcome>, Error> {
+    let outcome = match prepared {
+        PreparedFile::Built {
+            graph,
+            file,
+            mut partials,
+            paths,
+            syntax_errors,
+        } => {
+            writer.clean_file(&pending.path)?;
+            writer.store_result_for_file(&graph, file, &pending.tag, &mut partials, &paths)?;
+            FileOutcome::Built {
+                nodes: graph.nodes_for_file(file).count(),
+                syntax_errors,
+            }
+        }
+        PreparedFile::Failed(error) => {
+            writer.clean_file(&pending.path)?;
+            writer.store_error_for_file(&pending.path, &pending.tag, &error.message())?;
+            FileOutcome::Failed { error }
+        }
+        PreparedFile::TimedOut => {
+            writer.clean_file(&pending.path)?;
+            writer.store_error_for_file(&pending.path, &pending.tag, "timed out")?;
+            FileOutcome::TimedOut
+        }
+        PreparedFile::Cancelled => return Ok(None),
+    };
+    Ok(Some(outcome))
+}
+
+/// Status of one file in the index
+pub struct IndexEntry {
+    pub path: PathBuf,
+    pub tag: String,
+    pub status: FileStatus,
+}
+
+/// Lists the index entries below the given paths, or all entries if none are given
+pub fn status(database: &Path, paths: &[PathBuf]) -> Result<Vec<IndexEntry>, Error> {
+    let mut reader = SQLiteReader::open(database)?;
+    let mut entries = Vec::new();
+
+    let roots = if paths.is_empty() {
+        vec![None]
+    } else {
+        paths
+            .iter()
+            .map(|path| Some(files::normalize(path)))
+            .collect()
+    };
+    for root in roots {
+        // list_file_or_directory と list_all は型が異なるので、それぞれで集める
+        let found = match root {
+            Some(path) => reader
+                .list_file_or_directory(&path)?
+                .try_iter()?
+                .collect::<Result<Vec<_>, _>>()?,
+            None => reader
+                .list_all()?
+                .try_iter()?
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        entries.extend(found.into_iter().map(|entry| IndexEntry {
+            path: entry.path,
+            tag: entry.tag,
+            status: entry.status,
+        }));
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries.dedup_by(|a, b| a.path == b.path);
+    Ok(entries)
+}
+
+/// Removes the index entries below the given paths, or the whole index if none are given
+///
+/// # Returns
+/// The number of removed file entries
+pub fn clean(database: &Path, paths: &[PathBuf]) -> Result<usize, Error> {
+    let mut writer = SQLiteWriter::open(database)?;
+    if paths.is_empty() {
+        return Ok(writer.clean_all()?);
+    }
+
+    let mut removed = 0;
+    for path in paths {
+        removed += writer.clean_file_or_directory(&files::normalize(path))?;
+    }
+    Ok(removed)
+}
+
+/// Resolves the reference at a position using only the indexed data
+///
+/// Only the graph of the position's file is loaded up front; the partial paths and
+/// graphs of other files are loaded from the database as stitching reaches them.
+///
+/// # Returns
+/// `None` if there is no reference at the position
+pub fn resolve(database: &Path, position: &Position) -> Result<Option<Resolution>, Error> {
+    let mut reader = SQLiteReader::open(database)?;
+    let path = files::normalize(&position.path);
+    let file_name = path.to_string_lossy();
+    // status_for_file はタグなしで呼ぶと存在しない列を参照するので、一覧から探す
+    let status = reader
+        .list_file_or_directory(&path)?
+        .try_iter()?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .find(|entry| entry.path == path)
+        .map_or(FileStatus::Missing, |entry| entry.status);
+    match status {
+        FileStatus::Indexed => {}
+        FileStatus::Missing => return Err(Error::NotIndexed(path.clone())),
+        FileStatus::Error(message) => {
+            return Err(Error::Build {
+                path: path.clone(),
+                message,
+            })
+        }
+    }
+
+    let file = reader.load_graph_for_file(&file_name)?;
+    let (graph, _, _) = reader.get();
+    let Some(reference) = query::reference_at(graph, file, position.line, position.column) else {
+        return Ok(None);
+    };
+    let symbol = query::node_symbol(graph, reference)
+        .unwrap_or_default()
+        .to_string();
+
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut reader,
+        vec![reference],
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_graph, _partials, path| paths.push(path.clone()),
+    )?;
+    let (graph, partials, _) = reader.get();
+    let mut definitions = query::remove_shadowed(partials, paths)
+        .into_iter()
+        .filter_map(|path| Location::of_node(graph, path.end_node))
+        .collect::<Vec<_>>();
+    definitions.sort();
+    definitions.dedup();
+
+    Ok(Some(Resolution {
+        symbol,
+        definitions,
+    }))
+}
+
+/// Checks the references of indexed files using only the indexed data
+///
+/// Each file's graph is loaded in turn and its references are stitched against
+/// the partial paths in the database, the same way [`resolve`] does for a
+/// single reference. Files whose entry records an error are skipped.
+///
+/// # Arguments
+/// * `database` - Path of the SQLite database
+/// * `paths` - Files or directories in the database, or all files if empty
+///
+/// # Returns
+/// The report of the references that have no definitions
+pub fn unresolved(database: &Path, paths: &[PathBuf]) -> Result<UnresolvedReport, Error> {
+    let entries = status(database, paths)?;
+    let mut reader = SQLiteReader::open(database)?;
+    let mut report = UnresolvedReport::default();
+
+    for entry in entries {
+        if !matches!(entry.status, FileStatus::Indexed) {
+            continue;
+        }
+        let file = reader.load_graph_for_file(&entry.path.to_string_lossy())?;
+        let (graph, _, _) = reader.get();
+        let references = graph
+            .nodes_for_file(file)
+            .filter(|&node| graph[node].is_reference())
+            .collect::<Vec<_>>();
+
+        let mut resolved = HashSet::new();
+        ForwardPartialPathStitcher::find_all_complete_partial_paths(
+            &mut reader,
+            references.clone(),
+            StitcherConfig::default(),
+            &NoCancellation,
+            |_graph, _partials, path| {
+                resolved.insert(path.start_node);
+            },
+        )?;
+
+        let unresolved = references
+            .iter()
+            .copied()
+            .filter(|reference| !resolved.contains(reference))
+            .collect::<Vec<_>>();
+        let (graph, _, _) = reader.get();
+        report.add(graph, references.len(), &unresolved);
+    }
+    Ok(report)
+}
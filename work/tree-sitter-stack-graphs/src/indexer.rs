@@ -0,0 +1,183 @@
+//! Building and indexing stack graphs for a set of languages
+
+use std::path::{Path, PathBuf};
+
+use stack_graphs::graph::StackGraph;
+use tree_sitter_stack_graphs::NoCancellation;
+
+use crate::analyze::{self, BuildOptions, FileReport};
+use crate::assertions::{self, FixtureReport};
+use crate::error::Error;
+use crate::files;
+use crate::index::{self, Index, IndexOptions, IndexSummary};
+use crate::lsp;
+use crate::rules::{self, LanguageFile, LanguageRegistry};
+
+/// Builds stack graphs from source files, in memory or into a database
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use stack_graph_tools::{Indexer, Query};
+///
+/// let indexer = Indexer::new()?;
+/// let analysis = indexer.build(&[PathBuf::from("project")])?;
+/// let query = Query::new(&analysis.graph);
+/// # Ok::<(), stack_graph_tools::Error>(())
+/// ```
+pub struct Indexer {
+    languages: LanguageRegistry,
+    options: BuildOptions,
+}
+
+/// A graph built by [`Indexer::build`]
+pub struct Analysis {
+    /// The combined graph of all files that were built
+    pub graph: StackGraph,
+    /// The files collected from the given paths
+    pub files: Vec<PathBuf>,
+    /// One report per processed file
+    pub reports: Vec<FileReport>,
+}
+
+impl Analysis {
+    /// Returns the reports of the files that did not make it into the graph
+    pub fn failures(&self) -> impl Iterator<Item = &FileReport> {
+        self.reports
+            .iter()
+            .filter(|report| report.outcome.is_failure())
+    }
+
+    /// Returns the paths of the files that were built
+    pub fn built_files(&self) -> Vec<PathBuf> {
+        self.reports
+            .iter()
+            .filter(|report| !report.outcome.is_failure())
+            .map(|report| report.path.clone())
+            .collect()
+    }
+}
+
+impl Indexer {
+    /// Creates an indexer for the built-in languages and rules, without builtins
+    pub fn new() -> Result<Indexer, Error> {
+        Indexer::with_rules(&[], false, &[])
+    }
+
+    /// Creates an indexer whose languages use the given rules and builtins
+    ///
+    /// # Arguments
+    /// * `rules` - TSG rules files replacing the built-in rules of their language
+    /// * `builtins` - Whether to add the built-in builtins stubs to every graph
+    /// * `stubs` - Builtins stubs replacing the built-in ones; implies `builtins`
+    ///   for their language
+    pub fn with_rules(
+        rules: &[LanguageFile],
+        builtins: bool,
+        stubs: &[LanguageFile],
+    ) -> Result<Indexer, Error> {
+        Ok(Indexer::with_languages(rules::load_languages(
+            rules, builtins, stubs,
+        )?))
+    }
+
+    /// Creates an indexer for an already loaded set of languages
+    pub fn with_languages(languages: LanguageRegistry) -> Indexer {
+        Indexer {
+            languages,
+            options: BuildOptions::default(),
+        }
+    }
+
+    /// Sets the failure handling, per-file timeout and tolerance of syntax
+    /// errors of [`build`](Indexer::build) and [`build_source`](Indexer::build_source)
+    pub fn with_options(mut self, options: BuildOptions) -> Indexer {
+        self.options = options;
+        self
+    }
+
+    /// Returns the languages used to build graphs
+    pub fn languages(&self) -> &LanguageRegistry {
+        &self.languages
+    }
+
+    /// Collects the source files of all languages reachable from the given paths
+    pub fn collect_files(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error> {
+        files::collect_source_files(paths, &self.languages.extensions())
+    }
+
+    /// Builds one graph out of the source files reachable from the given paths
+    ///
+    /// # Returns
+    /// The graph with the per-file reports; files that fail to build are
+    /// reported rather than returned as errors
+    pub fn build(&self, paths: &[PathBuf]) -> Result<Analysis, Error> {
+        let files = self.collect_files(paths)?;
+        let (graph, reports) = analyze::build_graph(&self.languages, paths, &files, self.options);
+        Ok(Analysis {
+            graph,
+            files,
+            reports,
+        })
+    }
+
+    /// Builds the graph of a single file from source held in memory
+    ///
+    /// The file is the only module of its project, so its module path is its
+    /// file name. Builtins stubs are not added. With the `tolerant` option, the
+    /// syntax errors of the file are left out rather than returned.
+    pub fn build_source(&self, path: &Path, source: &str) -> Result<StackGraph, Error> {
+        let Some(language) = self.languages.for_path(path) else {
+            return Err(Error::Build {
+                path: path.to_path_buf(),
+                message: analyze::UNKNOWN_LANGUAGE.to_string(),
+            });
+        };
+        let root = files::source_root(&[], path);
+        let mut graph = StackGraph::new();
+        if self.options.tolerant {
+            analyze::build_source_tolerant(
+                language,
+                &mut graph,
+                path,
+                &root,
+                source,
+                &NoCancellation,
+            )?;
+        } else {
+            analyze::build_source(language, &mut graph, path, &root, source, &NoCancellation)?;
+        }
+        Ok(graph)
+    }
+
+    /// Brings an index up to date with the given paths
+    ///
+    /// Only files whose contents, rules, builtins stub, tolerant mode or path
+    /// relative to their root changed are rebuilt, and entries of files
+    /// that no longer exist are removed; failures of single files are part of
+    /// the summary.
+    pub fn index(
+        &self,
+        index: &Index,
+        paths: &[PathBuf],
+        options: IndexOptions,
+    ) -> Result<IndexSummary, Error> {
+        let files = self.collect_files(paths)?;
+        index::index_files(&self.languages, index.path(), paths, &files, options)
+    }
+
+    /// Checks the `defined:` assertions of fixture files, all built into one graph
+    ///
+    /// # Returns
+    /// One report per fixture file
+    pub fn check_fixtures(&self, paths: &[PathBuf]) -> Result<Vec<FixtureReport>, Error> {
+        let files = self.collect_files(paths)?;
+        Ok(assertions::check_fixtures(&self.languages, paths, &files))
+    }
+
+    /// Runs a language server for the indexer's languages on stdin and stdout
+    ///
+    /// Returns after the client sent `shutdown` and `exit`.
+    pub fn serve_lsp(&self) -> Result<(), Error> {
+        lsp::serve(&self.languages)
+    }
+}
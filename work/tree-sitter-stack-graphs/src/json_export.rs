@@ -0,0 +1,381 @@
+//! JSON export and import of stack graphs
+//!
+//! The schema is versioned by [`SCHEMA_VERSION`] and documented on the types
+//! below. Files, symbols, nodes and edges are sorted, so exporting the same graph
+//! twice gives the same JSON:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "files": ["functions.py"],
+//!   "symbols": ["greet"],
+//!   "nodes": [
+//!     { "id": { "local_id": 1 }, "kind": "root" },
+//!     {
+//!       "id": { "file": "functions.py", "local_id": 3 },
+//!       "kind": "pop_symbol",
+//!       "symbol": "greet",
+//!       "definition": true,
+//!       "span": { "start": { "line": 1, "column": 5 }, "end": { "line": 1, "column": 10 } },
+//!       "syntax_type": "function"
+//!     }
+//!   ],
+//!   "edges": [
+//!     {
+//!       "source": { "local_id": 1 },
+//!       "sink": { "file": "functions.py", "local_id": 3 },
+//!       "precedence": 0
+//!     }
+//!   ]
+//! }
+//! ```
+
+use std::collections::{BTreeSet, HashMap};
+
+use lsp_positions::{Offset, Span};
+use serde::{Deserialize, Serialize};
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{File, Node, NodeID, StackGraph};
+
+use crate::error::Error;
+use crate::export;
+use crate::query::{self, Location};
+
+/// Version of the JSON schema written by [`to_json`]
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A whole stack graph
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphJson {
+    /// Always [`SCHEMA_VERSION`]
+    pub version: u32,
+    /// Names of all files, sorted
+    pub files: Vec<String>,
+    /// All symbols used by nodes, sorted
+    pub symbols: Vec<String>,
+    /// All nodes, sorted by file and local ID; the root and jump-to nodes come first
+    pub nodes: Vec<NodeJson>,
+    /// All edges, sorted by source and sink
+    pub edges: Vec<EdgeJson>,
+}
+
+/// Identifies a node by its file and its ID within that file
+///
+/// The root and jump-to nodes belong to no file and have the local IDs 1 and 2.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct NodeIdJson {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    pub local_id: u32,
+}
+
+/// A node with its kind, symbol, flags and source span
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeJson {
+    pub id: NodeIdJson,
+    /// One of the kinds returned by [`export::node_kind`]
+    pub kind: String,
+    /// Symbol pushed or popped by the node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// Scope attached by a `push_scoped_symbol` node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<NodeIdJson>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub definition: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub reference: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub exported: bool,
+    /// Source range, omitted for nodes without a source node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<SpanJson>,
+    /// Syntax type given in the TSG rules, e.g. `function` or `variable`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syntax_type: Option<String>,
+}
+
+/// A source range with 1-based lines and columns; columns count UTF-8 bytes
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpanJson {
+    pub start: PointJson,
+    pub end: PointJson,
+}
+
+/// A 1-based line and column
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PointJson {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// An edge with its precedence
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdgeJson {
+    pub source: NodeIdJson,
+    pub sink: NodeIdJson,
+    pub precedence: i32,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Converts a StackGraph to its JSON representation
+///
+/// # Arguments
+/// * `graph` - Reference to the StackGraph to export
+///
+/// # Returns
+/// The graph in the schema described in the module documentation
+pub fn to_graph_json(graph: &StackGraph) -> GraphJson {
+    let mut files = graph
+        .iter_files()
+        .map(|file| graph[file].name().to_string())
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let mut symbols = BTreeSet::new();
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    for node in graph.iter_nodes() {
+        let json = node_json(graph, node);
+        if let Some(symbol) = &json.symbol {
+            symbols.insert(symbol.clone());
+        }
+        nodes.push(json);
+
+        for edge in graph.outgoing_edges(node) {
+            edges.push(EdgeJson {
+                source: node_id_json(graph, graph[node].id()),
+                sink: node_id_json(graph, graph[edge.sink].id()),
+                precedence: edge.precedence,
+            });
+        }
+    }
+
+    // ハンドルの順序に依存しないように並べ替える
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    edges.sort_by(|a, b| (&a.source, &a.sink).cmp(&(&b.source, &b.sink)));
+
+    GraphJson {
+        version: SCHEMA_VERSION,
+        files,
+        symbols: symbols.into_iter().collect(),
+        nodes,
+        edges,
+    }
+}
+
+/// Converts a StackGraph to pretty-printed JSON
+///
+/// # Arguments
+/// * `graph` - Reference to the StackGraph to export
+///
+/// # Returns
+/// A String containing the JSON representation of the graph
+pub fn to_json(graph: &StackGraph) -> String {
+    serde_json::to_string_pretty(&to_graph_json(graph)).expect("graph JSON is serializable")
+}
+
+/// ノード1つ分の JSON を組み立てる
+fn node_json(graph: &StackGraph, node: Handle<Node>) -> NodeJson {
+    let data = &graph[node];
+    let scope = match data {
+        Node::PushScopedSymbol(push) => Some(node_id_json(graph, push.scope)),
+        _ => None,
+    };
+    let span = Location::of_node(graph, node)
+        .filter(|location| !location.is_empty())
+        .map(|location| SpanJson {
+            start: PointJson {
+                line: location.start_line,
+                column: location.start_column,
+            },
+            end: PointJson {
+                line: location.end_line,
+                column: location.end_column,
+            },
+        });
+    let syntax_type = graph
+        .source_info(node)
+        .and_then(|info| info.syntax_type.into_option())
+        .map(|syntax_type| graph[syntax_type].to_string());
+
+    NodeJson {
+        id: node_id_json(graph, data.id()),
+        kind: export::node_kind(data).to_string(),
+        symbol: query::node_symbol(graph, node).map(str::to_string),
+        scope,
+        definition: data.is_definition(),
+        reference: data.is_reference(),
+        exported: data.is_exported_scope(),
+        span,
+        syntax_type,
+    }
+}
+
+/// Returns the JSON identifier of a node
+pub fn node_id_json(graph: &StackGraph, id: NodeID) -> NodeIdJson {
+    NodeIdJson {
+        file: id.file().map(|file| graph[file].name().to_string()),
+        local_id: id.local_id(),
+    }
+}
+
+/// Rebuilds a StackGraph from JSON written by [`Exporter::to_json`](crate::Exporter::to_json)
+///
+/// Spans are restored with their lines and UTF-8 columns only; UTF-16 and
+/// grapheme offsets and the containing lines are not part of the schema.
+///
+/// # Arguments
+/// * `json` - The JSON text
+///
+/// # Returns
+/// The rebuilt graph, or an error if the JSON does not follow the schema or
+/// describes an inconsistent graph, as [`Error::Json`]
+pub fn from_json(json: &str) -> Result<StackGraph, Error> {
+    let graph_json: GraphJson =
+        serde_json::from_str(json).map_err(|err| Error::Json(err.to_string()))?;
+    from_graph_json(&graph_json).map_err(Error::Json)
+}
+
+/// Rebuilds a StackGraph from its JSON representation
+///
+/// Takes the value returned by [`to_graph_json`]; see [`from_json`].
+pub fn from_graph_json(graph_json: &GraphJson) -> Result<StackGraph, String> {
+    if graph_json.version != SCHEMA_VERSION {
+        return Err(format!(
+            "unsupported schema version {}, expected {}",
+            graph_json.version, SCHEMA_VERSION
+        ));
+    }
+
+    let mut graph = StackGraph::new();
+    let mut files = HashMap::new();
+    for name in &graph_json.files {
+        files.insert(name.as_str(), graph.get_or_create_file(name));
+    }
+
+    let mut handles = HashMap::new();
+    handles.insert(
+        node_id_json(&graph, NodeID::root()),
+        StackGraph::root_node(),
+    );
+    handles.insert(
+        node_id_json(&graph, NodeID::jump_to()),
+        StackGraph::jump_to_node(),
+    );
+
+    for node in &graph_json.nodes {
+        if node.id.file.is_none() {
+            // ルートとジャンプ先のノードは最初から存在する
+            if handles.contains_key(&node.id) {
+                continue;
+            }
+            return Err(format!("node {} has no file", node.id.local_id));
+        }
+        let handle = add_node(&mut graph, &files, node)?;
+        if let Some(span) = &node.span {
+            graph.source_info_mut(handle).span = Span {
+                start: position(span.start),
+                end: position(span.end),
+            };
+        }
+        if let Some(syntax_type) = &node.syntax_type {
+            let syntax_type = graph.add_string(syntax_type);
+            graph.source_info_mut(handle).syntax_type = syntax_type.into();
+        }
+        handles.insert(node.id.clone(), handle);
+    }
+
+    for edge in &graph_json.edges {
+        let source = lookup(&handles, &edge.source)?;
+        let sink = lookup(&handles, &edge.sink)?;
+        graph.add_edge(source, sink, edge.precedence);
+    }
+
+    Ok(graph)
+}
+
+/// JSON のノードを種類に応じてグラフに追加する
+fn add_node(
+    graph: &mut StackGraph,
+    files: &HashMap<&str, Handle<File>>,
+    node: &NodeJson,
+) -> Result<Handle<Node>, String> {
+    let id = node_id(files, &node.id)?;
+    let symbol = |graph: &mut StackGraph| match &node.symbol {
+        Some(symbol) => Ok(graph.add_symbol(symbol)),
+        None => Err(format!(
+            "{} node {} has no symbol",
+            node.kind, node.id.local_id
+        )),
+    };
+
+    let handle = match node.kind.as_str() {
+        "scope" => graph.add_scope_node(id, node.exported),
+        "push_symbol" => {
+            let symbol = symbol(graph)?;
+            graph.add_push_symbol_node(id, symbol, node.reference)
+        }
+        "pop_symbol" => {
+            let symbol = symbol(graph)?;
+            graph.add_pop_symbol_node(id, symbol, node.definition)
+        }
+        "push_scoped_symbol" => {
+            let symbol = symbol(graph)?;
+            let scope = match &node.scope {
+                Some(scope) => node_id(files, scope)?,
+                None => {
+                    return Err(format!(
+                        "push_scoped_symbol node {} has no scope",
+                        id.local_id()
+                    ))
+                }
+            };
+            graph.add_push_scoped_symbol_node(id, symbol, scope, node.reference)
+        }
+        "pop_scoped_symbol" => {
+            let symbol = symbol(graph)?;
+            graph.add_pop_scoped_symbol_node(id, symbol, node.definition)
+        }
+        "drop_scopes" => graph.add_drop_scopes_node(id),
+        kind => return Err(format!("unknown node kind `{}`", kind)),
+    };
+    handle.ok_or_else(|| format!("duplicate node {} in {:?}", id.local_id(), node.id.file))
+}
+
+fn node_id(files: &HashMap<&str, Handle<File>>, id: &NodeIdJson) -> Result<NodeID, String> {
+    match &id.file {
+        Some(name) => match files.get(name.as_str()) {
+            Some(&file) => Ok(NodeID::new_in_file(file, id.local_id)),
+            None => Err(format!("unknown file `{}`", name)),
+        },
+        None if id.local_id == NodeID::root().local_id() => Ok(NodeID::root()),
+        None if id.local_id == NodeID::jump_to().local_id() => Ok(NodeID::jump_to()),
+        None => Err(format!("node {} has no file", id.local_id)),
+    }
+}
+
+fn lookup(
+    handles: &HashMap<NodeIdJson, Handle<Node>>,
+    id: &NodeIdJson,
+) -> Result<Handle<Node>, String> {
+    handles
+        .get(id)
+        .copied()
+        .ok_or_else(|| format!("edge refers to unknown node {:?}", id))
+}
+
+/// 1 始まりの位置を lsp_positions の 0 始まりの位置に戻す
+fn position(point: PointJson) -> lsp_positions::Position {
+    lsp_positions::Position {
+        line: point.line.saturating_sub(1),
+        column: Offset {
+            utf8_offset: point.column.saturating_sub(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
@@ -0,0 +1,64 @@
+//! Stack graphs for Python and JavaScript sources
+//!
+//! The `tree-sitter-stack-graphs` binary is a thin command line wrapper over
+//! this library; other tools can use the same API directly:
+//!
+//! - [`Indexer`] builds graphs from source files in memory or into an
+//!   [`Index`] database, using the TSG rules of every registered language.
+//! - [`Query`] resolves references, finds references to definitions, lists
+//!   unresolved references and traces resolutions in a built graph.
+//! - [`Exporter`] renders a graph as DOT, Mermaid, GraphML, HTML, JSON or
+//!   canonical text.
+//!
+//! All fallible operations return the crate's [`Error`]. The modules behind
+//! these types are private; only the types they work with are exported.
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//! use stack_graph_tools::{Indexer, Position, Query};
+//!
+//! let indexer = Indexer::new()?;
+//! let analysis = indexer.build(&[PathBuf::from("project")])?;
+//! let query = Query::new(&analysis.graph);
+//! let position = "project/main.py:3:1".parse::<Position>().unwrap();
+//! if let Some(resolution) = query.definitions(&position)? {
+//!     for location in &resolution.definitions {
+//!         println!("{}: definition of `{}`", location, resolution.symbol);
+//!     }
+//! }
+//! # Ok::<(), stack_graph_tools::Error>(())
+//! ```
+
+pub(crate) mod analyze;
+pub(crate) mod assertions;
+pub(crate) mod builtins;
+pub(crate) mod cancel;
+pub(crate) mod canonical;
+pub(crate) mod diagnostics;
+pub(crate) mod error;
+pub(crate) mod export;
+pub(crate) mod files;
+pub(crate) mod index;
+pub(crate) mod indexer;
+pub(crate) mod json_export;
+pub(crate) mod lsp;
+pub(crate) mod query;
+pub(crate) mod rules;
+pub(crate) mod stats;
+pub(crate) mod trace;
+pub(crate) mod unresolved;
+
+pub use analyze::{BuildOptions, FileOutcome, FileReport};
+pub use assertions::{AssertionFailure, FixtureReport};
+pub use cancel::{install_interrupt_handler, interrupted};
+pub use diagnostics::{Excerpt, SyntaxError};
+pub use error::Error;
+pub use export::{ExportFormat, ExportOptions, Exporter};
+pub use index::{Index, IndexEntry, IndexOptions, IndexSummary};
+pub use indexer::{Analysis, Indexer};
+pub use json_export::from_json as graph_from_json;
+pub use query::{Location, Position, Query, ReferenceSite, References, Resolution};
+pub use rules::{LanguageFile, LanguageRegistry};
+pub use stats::{Counts, GraphStats};
+pub use trace::{Trace, TraceStep, TraceStop};
+pub use unresolved::{UnresolvedReference, UnresolvedReport};
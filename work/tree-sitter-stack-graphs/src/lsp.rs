@@ -0,0 +1,448 @@
+//! Language server speaking LSP over stdio
+//!
+//! On `initialize` the server builds the stack graph of every source file in the
+//! workspace folder, each file into its own `StackGraph`. Open documents are
+//! kept in memory: `didOpen`, `didChange` (full text sync) and `didClose` rebuild
+//! the graph of that one file and merge the per-file graphs again. Requests are
+//! answered from the merged graph. Files with syntax errors, as they are while
+//! being edited, are built from their well-formed parts:
+//!
+//! * `textDocument/definition` resolves the reference at the position
+//! * `textDocument/references` lists the references to the definition at the
+//!   position, or to the definitions of the reference at the position
+//! * `textDocument/hover` shows the symbol with the kinds and locations of its
+//!   definitions
+//!
+//! LSP positions count UTF-16 code units from 0, while the graph uses 1-based
+//! lines and UTF-8 byte columns; positions are converted with the file's text.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types as lsp;
+use lsp_types::notification::Notification as _;
+use lsp_types::request::Request as _;
+use rayon::prelude::*;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{File, Node, StackGraph};
+use tree_sitter_stack_graphs::NoCancellation;
+
+use crate::analyze;
+use crate::error::Error;
+use crate::files;
+use crate::query::{self, Location};
+use crate::rules::LanguageRegistry;
+
+/// Serves one client on stdin and stdout until it sends `exit`
+///
+/// # Arguments
+/// * `languages` - The languages whose TSG rules are run, chosen per file by
+///   its extension
+///
+/// # Returns
+/// [`Error::Lsp`] if the connection to the client fails
+pub fn serve(languages: &LanguageRegistry) -> Result<(), Error> {
+    serve_stdio(languages).map_err(|err| Error::Lsp(err.to_string()))
+}
+
+/// 接続を確立してリクエストを処理する。接続のエラーは型が様々なので箱に入れて返す
+fn serve_stdio(languages: &LanguageRegistry) -> Result<(), Box<dyn std::error::Error>> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = lsp::ServerCapabilities {
+        text_document_sync: Some(lsp::TextDocumentSyncCapability::Kind(
+            lsp::TextDocumentSyncKind::FULL,
+        )),
+        definition_provider: Some(lsp::OneOf::Left(true)),
+        references_provider: Some(lsp::OneOf::Left(true)),
+        hover_provider: Some(lsp::HoverProviderCapability::Simple(true)),
+        ..Default::default()
+    };
+    let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let params: lsp::InitializeParams = serde_json::from_value(params)?;
+
+    let mut workspace = Workspace::new(languages, workspace_root(&params)?);
+    workspace.index();
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                let response = workspace.handle_request(request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => workspace.handle_notification(notification),
+            Message::Response(_) => {}
+        }
+    }
+
+    // 送信側を閉じないと書き込みスレッドが終わらない
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// 最初のワークスペースフォルダ、なければ rootUri、どちらもなければカレントディレクトリ
+#[allow(deprecated)]
+fn workspace_root(params: &lsp::InitializeParams) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let uri = params
+        .workspace_folders
+        .as_ref()
+        .and_then(|folders| folders.first())
+        .map(|folder| &folder.uri)
+        .or(params.root_uri.as_ref());
+    match uri {
+        Some(uri) => uri
+            .to_file_path()
+            .map_err(|_| format!("{}: workspace is not a local directory", uri).into()),
+        None => Ok(std::env::current_dir()?),
+    }
+}
+
+/// ワークスペース内の1ファイル分のテキストとグラフ
+struct Document {
+    source: String,
+    /// このファイルだけから構築したグラフ。構築に失敗したファイルは持たない
+    graph: Option<StackGraph>,
+}
+
+/// The files of the workspace and the graph merged from them
+struct Workspace<'a> {
+    languages: &'a LanguageRegistry,
+    root: PathBuf,
+    documents: BTreeMap<PathBuf, Document>,
+    graph: StackGraph,
+}
+
+impl<'a> Workspace<'a> {
+    fn new(languages: &'a LanguageRegistry, root: PathBuf) -> Self {
+        Workspace {
+            languages,
+            root,
+            documents: BTreeMap::new(),
+            graph: StackGraph::new(),
+        }
+    }
+
+    /// ワークスペース内の全ファイルを並列に構築する
+    fn index(&mut self) {
+        let paths = match files::collect_source_files(
+            std::slice::from_ref(&self.root),
+            &self.languages.extensions(),
+        ) {
+            Ok(paths) => paths,
+            Err(err) => {
+                eprintln!("{}: error: {}", self.root.display(), err);
+                Vec::new()
+            }
+        };
+        let (languages, root) = (self.languages, &self.root);
+        let documents = paths
+            .par_iter()
+            .filter_map(|path| {
+                let source = fs::read_to_string(path)
+                    .map_err(|err| eprintln!("{}: error: {}", path.display(), err))
+                    .ok()?;
+                let graph = build(languages, root, path, &source);
+                Some((path.clone(), Document { source, graph }))
+            })
+            .collect::<Vec<_>>();
+        self.documents.extend(documents);
+        self.merge();
+    }
+
+    /// 組み込み名の後にファイルごとのグラフを1つにまとめる
+    fn merge(&mut self) {
+        let mut graph = StackGraph::new();
+        self.languages.add_builtins(&mut graph);
+        for document in self.documents.values() {
+            if let Some(file_graph) = &document.graph {
+                // ファイル名はパスごとに一意なので重複しない
+                let _ = graph.add_from_graph(file_graph);
+            }
+        }
+        self.graph = graph;
+    }
+
+    /// ファイルのテキストを置き換えてそのファイルだけを構築し直す
+    fn update(&mut self, uri: &lsp::Url, source: String) {
+        let Ok(path) = uri.to_file_path() else {
+            return;
+        };
+        if self.languages.for_path(&path).is_none() {
+            return;
+        }
+        let graph = build(self.languages, &self.root, &path, &source);
+        self.documents.insert(path, Document { source, graph });
+        self.merge();
+    }
+
+    fn handle_notification(&mut self, notification: Notification) {
+        match notification.method.as_str() {
+            lsp::notification::DidOpenTextDocument::METHOD => {
+                if let Ok(params) = notification.extract::<lsp::DidOpenTextDocumentParams>(
+                    lsp::notification::DidOpenTextDocument::METHOD,
+                ) {
+                    self.update(&params.text_document.uri, params.text_document.text);
+                }
+            }
+            lsp::notification::DidChangeTextDocument::METHOD => {
+                if let Ok(params) = notification.extract::<lsp::DidChangeTextDocumentParams>(
+                    lsp::notification::DidChangeTextDocument::METHOD,
+                ) {
+                    // 全文同期なので最後の変更が現在のテキスト
+                    if let Some(change) = params.content_changes.into_iter().last() {
+                        self.update(&params.text_document.uri, change.text);
+                    }
+                }
+            }
+            lsp::notification::DidCloseTextDocument::METHOD => {
+                if let Ok(params) = notification.extract::<lsp::DidCloseTextDocumentParams>(
+                    lsp::notification::DidCloseTextDocument::METHOD,
+                ) {
+                    // 保存されていない変更を捨てて、ディスク上の内容に戻す
+                    let uri = params.text_document.uri;
+                    if let Some(source) = uri
+                        .to_file_path()
+                        .ok()
+                        .and_then(|path| fs::read_to_string(path).ok())
+                    {
+                        self.update(&uri, source);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            lsp::request::GotoDefinition::METHOD => request
+                .extract::<lsp::GotoDefinitionParams>(lsp::request::GotoDefinition::METHOD)
+                .map_err(|err| err.to_string())
+                .and_then(|(_, params)| self.definition(params))
+                .and_then(to_value),
+            lsp::request::References::METHOD => request
+                .extract::<lsp::ReferenceParams>(lsp::request::References::METHOD)
+                .map_err(|err| err.to_string())
+                .and_then(|(_, params)| self.references(params))
+                .and_then(to_value),
+            lsp::request::HoverRequest::METHOD => request
+                .extract::<lsp::HoverParams>(lsp::request::HoverRequest::METHOD)
+                .map_err(|err| err.to_string())
+                .and_then(|(_, params)| self.hover(params))
+                .and_then(to_value),
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported method `{}`", method),
+                )
+            }
+        };
+        respond(id, result)
+    }
+
+    /// LSP の位置をファイルのハンドルと 1 始まりの行・UTF-8 列に変換する
+    fn position(
+        &self,
+        params: &lsp::TextDocumentPositionParams,
+    ) -> Option<(Handle<File>, usize, usize)> {
+        let path = params.text_document.uri.to_file_path().ok()?;
+        let file = self.graph.get_file(&path.to_string_lossy())?;
+        let line_text = self
+            .documents
+            .get(&path)?
+            .source
+            .lines()
+            .nth(params.position.line as usize)
+            .unwrap_or_default();
+        let column = utf8_column(line_text, params.position.character);
+        Some((file, params.position.line as usize + 1, column + 1))
+    }
+
+    fn definition(
+        &self,
+        params: lsp::GotoDefinitionParams,
+    ) -> Result<Option<lsp::GotoDefinitionResponse>, String> {
+        let Some((file, line, column)) = self.position(&params.text_document_position_params)
+        else {
+            return Ok(None);
+        };
+        let Some(reference) = query::reference_at(&self.graph, file, line, column) else {
+            return Ok(None);
+        };
+        let definitions =
+            query::find_definitions(&self.graph, reference).map_err(|err| err.to_string())?;
+        let locations = self.locations(&definitions);
+        Ok(Some(lsp::GotoDefinitionResponse::Array(locations)))
+    }
+
+    fn references(&self, params: lsp::ReferenceParams) -> Result<Vec<lsp::Location>, String> {
+        let Some((file, line, column)) = self.position(&params.text_document_position) else {
+            return Ok(Vec::new());
+        };
+
+        // 定義位置でなければ、その位置の参照が解決される定義を使う
+        let definitions = match query::definition_at(&self.graph, file, line, column) {
+            Some(definition) => vec![definition],
+            None => match query::reference_at(&self.graph, file, line, column) {
+                Some(reference) => query::find_definitions(&self.graph, reference)
+                    .map_err(|err| err.to_string())?,
+                None => Vec::new(),
+            },
+        };
+
+        let mut nodes = Vec::new();
+        for &definition in &definitions {
+            nodes.extend(
+                query::find_references(&self.graph, definition).map_err(|err| err.to_string())?,
+            );
+        }
+        if params.context.include_declaration {
+            nodes.extend(definitions);
+        }
+        nodes.sort();
+        nodes.dedup();
+        Ok(self.locations(&nodes))
+    }
+
+    fn hover(&self, params: lsp::HoverParams) -> Result<Option<lsp::Hover>, String> {
+        let Some((file, line, column)) = self.position(&params.text_document_position_params)
+        else {
+            return Ok(None);
+        };
+
+        let (node, definitions) = match query::reference_at(&self.graph, file, line, column) {
+            Some(reference) => (
+                reference,
+                query::find_definitions(&self.graph, reference).map_err(|err| err.to_string())?,
+            ),
+            None => match query::definition_at(&self.graph, file, line, column) {
+                Some(definition) => (definition, vec![definition]),
+                None => return Ok(None),
+            },
+        };
+
+        let symbol = query::node_symbol(&self.graph, node).unwrap_or_default();
+        let mut value = format!("`{}`", symbol);
+        if definitions.is_empty() {
+            value.push_str("\n\nNo definitions found");
+        }
+        for &definition in &definitions {
+            let Some(location) = Location::of_node(&self.graph, definition) else {
+                continue;
+            };
+            let kind = syntax_type(&self.graph, definition).unwrap_or("definition");
+            value.push_str(&format!(
+                "\n\n{} at `{}:{}:{}`",
+                kind,
+                files::relative_path(&self.root, Path::new(&location.file)),
+                location.start_line,
+                location.start_column
+            ));
+        }
+
+        Ok(Some(lsp::Hover {
+            contents: lsp::HoverContents::Markup(lsp::MarkupContent {
+                kind: lsp::MarkupKind::Markdown,
+                value,
+            }),
+            range: Location::of_node(&self.graph, node).and_then(|location| self.range(&location)),
+        }))
+    }
+
+    /// ノードを LSP の位置に変換する。ディスク上のファイルでないノードは除く
+    fn locations(&self, nodes: &[Handle<Node>]) -> Vec<lsp::Location> {
+        nodes
+            .iter()
+            .filter_map(|&node| Location::of_node(&self.graph, node))
+            .filter_map(|location| {
+                let uri = lsp::Url::from_file_path(&location.file).ok()?;
+                Some(lsp::Location::new(uri, self.range(&location)?))
+            })
+            .collect()
+    }
+
+    fn range(&self, location: &Location) -> Option<lsp::Range> {
+        let path = PathBuf::from(&location.file);
+        let disk_source;
+        let source = match self.documents.get(&path) {
+            Some(document) => &document.source,
+            None => {
+                disk_source = fs::read_to_string(&path).ok()?;
+                &disk_source
+            }
+        };
+        let position = |line: usize, column: usize| {
+            let line_text = source.lines().nth(line - 1).unwrap_or_default();
+            lsp::Position::new(line as u32 - 1, utf16_column(line_text, column - 1))
+        };
+        Some(lsp::Range::new(
+            position(location.start_line, location.start_column),
+            position(location.end_line, location.end_column),
+        ))
+    }
+}
+
+/// 1ファイル分のグラフを構築する。編集中のファイルは構文エラーを除いて構築し、失敗は標準エラーに出力する
+fn build(
+    languages: &LanguageRegistry,
+    workspace_root: &Path,
+    path: &Path,
+    source: &str,
+) -> Option<StackGraph> {
+    let language = languages.for_path(path)?;
+    let mut graph = StackGraph::new();
+    let root = files::source_root(&[workspace_root.to_path_buf()], path);
+    match analyze::build_source_tolerant(language, &mut graph, path, &root, source, &NoCancellation)
+    {
+        Ok(_) => Some(graph),
+        Err(err) => {
+            eprintln!("{}", err.display_pretty());
+            None
+        }
+    }
+}
+
+fn to_value<T: serde::Serialize>(result: T) -> Result<serde_json::Value, String> {
+    serde_json::to_value(result).map_err(|err| err.to_string())
+}
+
+fn respond(id: RequestId, result: Result<serde_json::Value, String>) -> Response {
+    match result {
+        Ok(value) => Response::new_ok(id, value),
+        Err(message) => Response::new_err(id, ErrorCode::RequestFailed as i32, message),
+    }
+}
+
+/// ノードの構文上の種類（`function` など）を返す
+fn syntax_type(graph: &StackGraph, node: Handle<Node>) -> Option<&str> {
+    let syntax_type = graph.source_info(node)?.syntax_type.into_option()?;
+    Some(&graph[syntax_type])
+}
+
+/// UTF-16 の列を行内の UTF-8 バイト位置に変換する
+fn utf8_column(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (offset, c) in line.char_indices() {
+        if units >= character as usize {
+            return offset;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// 行内の UTF-8 バイト位置を UTF-16 の列に変換する
+fn utf16_column(line: &str, byte: usize) -> u32 {
+    let byte = byte.min(line.len());
+    line.get(..byte)
+        .map(|prefix| prefix.encode_utf16().count())
+        .unwrap_or_default() as u32
+}
@@ -1,79 +1,653 @@
-use stack_graphs::graph::StackGraph;
-use tree_sitter_python::LANGUAGE;
-use tree_sitter_stack_graphs::{NoCancellation, StackGraphLanguage, Variables};
-
-mod dot_export;
-
-// TSGルールの定義
-// 基本的なPythonのimport文とモジュール参照を解析するルール
-const STACK_GRAPH_RULES: &str = r#"
-(module) @__tsg__full_match {
-    node scope_node
-    attr (scope_node) type = "scope"
-}
-
-(import_statement name: (dotted_name) @name) {
-    node scope_node
-    attr (scope_node) type = "scope"
-
-    node import_ref
-    attr (import_ref) type = "pop_symbol"
-    attr (import_ref) symbol = (source-text @name)
-    attr (import_ref) is_definition
-    edge scope_node -> import_ref
-}
-
-(attribute object: (identifier) @obj attribute: (identifier) @attr) {
-    node scope_node
-    attr (scope_node) type = "scope"
-
-    node ref_node
-    attr (ref_node) type = "push_symbol"
-    attr (ref_node) symbol = (source-text @obj)
-    
-    node attr_node
-    attr (attr_node) type = "pop_symbol"
-    attr (attr_node) symbol = (source-text @attr)
-    edge ref_node -> attr_node
-}
-"#;
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 解析対象のPythonソースコード
-    let python_source = r#"
-import sys
-print(sys.path)
-    "#;
-
-    // tree-sitter-pythonのLanguageを取得
-    let grammar = LANGUAGE.into();
-
-    // tree-sitter-stack-graphsのStackGraphLanguageを作成
-    let language = StackGraphLanguage::from_str(grammar, STACK_GRAPH_RULES)?;
-
-    // StackGraphインスタンス生成
-    let mut stack_graph = StackGraph::new();
-    let file_handle = stack_graph.get_or_create_file("sample.py");
-
-    // グローバル変数（ファイル名など）を設定
-    let globals = Variables::new();
-
-    // スタックグラフの構築
-    language.build_stack_graph_into(
-        &mut stack_graph,
-        file_handle,
-        python_source,
-        &globals,
-        &NoCancellation,
-    )?;
-
-    // 生成されたグラフの情報を出力
-    println!("Graph has {} nodes", stack_graph.iter_nodes().count());
-    println!("Generated stack graph for Python code analysis");
-
-    // DOT形式でグラフを出力
-    let dot_contents = dot_export::to_dot(&stack_graph);
-    println!("\nDOT representation:\n{}", dot_contents);
-
-    Ok(())
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand};
+use stack_graphs::storage::FileStatus;
+
+use stack_graph_tools::{
+    graph_from_json, install_interrupt_handler, interrupted, Analysis, BuildOptions, Error,
+    ExportFormat, ExportOptions, Exporter, FileOutcome, FileReport, GraphStats, Index,
+    IndexOptions, Indexer, LanguageFile, Position, Query,
+};
+
+/// Command line interface of the stack graph tool
+#[derive(Parser)]
+#[command(
+    name = "tree-sitter-stack-graphs",
+    about = "Build stack graphs for Python and JavaScript sources"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// TSG rules file to use instead of the built-in rules of a language, as
+    /// `LANGUAGE=FILE`; a bare `FILE` replaces the Python rules
+    #[arg(long, value_name = "[LANGUAGE=]FILE", global = true)]
+    rules: Vec<LanguageFile>,
+
+    /// Add synthetic definitions for builtin names and standard library
+    /// modules before the source files, so that references to them resolve
+    #[arg(long, global = true)]
+    builtins: bool,
+
+    /// Builtins stub to use instead of the built-in one of a language, as
+    /// `LANGUAGE=FILE`; a bare `FILE` replaces the Python stub. Implies `--builtins`
+    #[arg(long, value_name = "[LANGUAGE=]FILE", global = true)]
+    builtins_stub: Vec<LanguageFile>,
+
+    /// SQLite database holding the persisted stack graphs
+    #[arg(long, value_name = "FILE", global = true, default_value = Index::DEFAULT_PATH)]
+    database: PathBuf,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Build a stack graph from source files and report per-file node counts
+    Analyze(AnalyzeArgs),
+    /// Resolve the reference at a position to its definitions
+    Definition(DefinitionArgs),
+    /// List every reference that resolves to the definition at a position
+    References(PositionArgs),
+    /// Show every path explored while resolving the reference at a position
+    Trace(TraceArgs),
+    /// Store per-file stack graphs and partial paths in the database
+    Index(IndexArgs),
+    /// Show the index status of files in the database
+    Status(IndexedPathsArgs),
+    /// Remove files from the database
+    Clean(IndexedPathsArgs),
+    /// Convert a graph exported with `analyze --json` to another format
+    Convert(ConvertArgs),
+    /// Check the `# ^ defined:` assertions in fixture files
+    Test(TestArgs),
+    /// List every reference that does not resolve to any definition
+    Unresolved(UnresolvedArgs),
+    /// Report node, edge and symbol counts per file and in total
+    Stats(StatsArgs),
+    /// Run a language server on stdin and stdout
+    Lsp,
+}
+
+#[derive(Args)]
+struct AnalyzeArgs {
+    /// Files or directories to analyze (directories are walked for
+    /// files of every supported language)
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Continue with the remaining files when one of them fails
+    #[arg(long)]
+    keep_going: bool,
+
+    /// Give up on a file after this many seconds
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Build the well-formed parts of files with syntax errors, reporting the
+    /// errors as warnings instead of failing the files
+    #[arg(long)]
+    tolerant: bool,
+
+    /// Print the combined graph in DOT format
+    #[arg(long, group = "graph_output")]
+    dot: bool,
+
+    /// Print the combined graph as a Mermaid flowchart
+    #[arg(long, group = "graph_output")]
+    mermaid: bool,
+
+    /// Print the combined graph as GraphML
+    #[arg(long, group = "graph_output")]
+    graphml: bool,
+
+    /// Only include the nodes of this file in the printed graph (repeatable)
+    #[arg(long, value_name = "FILE", requires = "graph_output")]
+    dot_file: Vec<PathBuf>,
+
+    /// Hide scope nodes in the printed graph, drawing dashed edges across them
+    #[arg(long, requires = "graph_output")]
+    dot_hide_scopes: bool,
+
+    /// Only include the neighbourhood of the reference or definition at this position
+    #[arg(long, value_name = "FILE:LINE:COLUMN", requires = "graph_output")]
+    dot_focus: Option<Position>,
+
+    /// Number of hops around the `--dot-focus` node to include
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    dot_depth: usize,
+
+    /// Write the combined graph as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    json: Option<PathBuf>,
+
+    /// Write an HTML page that displays the combined graph to this file
+    #[arg(long, value_name = "FILE")]
+    html: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct PositionArgs {
+    /// Position in a source file, with 1-based line and column
+    #[arg(value_name = "FILE:LINE:COLUMN")]
+    position: Position,
+
+    /// Additional files or directories to index alongside FILE
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+struct DefinitionArgs {
+    #[command(flatten)]
+    target: PositionArgs,
+
+    /// Resolve from the database instead of building the graph from source
+    #[arg(long)]
+    use_index: bool,
+}
+
+#[derive(Args)]
+struct IndexArgs {
+    /// Files or directories to index (directories are walked for
+    /// files of every supported language)
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Continue with the remaining files when one of them fails
+    #[arg(long)]
+    keep_going: bool,
+
+    /// Re-index files even if their tag is unchanged
+    #[arg(long)]
+    force: bool,
+
+    /// Give up on a file after this many seconds
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Index the well-formed parts of files with syntax errors, reporting the
+    /// errors as warnings instead of failing the files
+    #[arg(long)]
+    tolerant: bool,
+}
+
+#[derive(Args)]
+struct TraceArgs {
+    #[command(flatten)]
+    target: PositionArgs,
+
+    /// Print the trace as JSON instead of a DOT overlay
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct IndexedPathsArgs {
+    /// Files or directories in the database (all files if omitted)
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+struct TestArgs {
+    /// Fixture files or directories; all of them are built into one graph
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+struct UnresolvedArgs {
+    /// Files or directories to check (directories are walked for files of every
+    /// supported language); with `--use-index`, all indexed files if omitted
+    #[arg(required_unless_present = "use_index")]
+    paths: Vec<PathBuf>,
+
+    /// Check the indexed files using the database instead of building the graph
+    #[arg(long)]
+    use_index: bool,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct StatsArgs {
+    /// Files or directories to examine (directories are walked for files of
+    /// every supported language)
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Number of most common symbols to list
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    top: usize,
+
+    /// Print the statistics as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    /// JSON file written by `analyze --json`
+    input: PathBuf,
+
+    /// Format printed to stdout: dot, mermaid, graphml, html, json or text
+    #[arg(long, value_name = "FORMAT", default_value_t = ExportFormat::Json)]
+    to: ExportFormat,
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(err) => {
+            match err.downcast_ref::<Error>() {
+                Some(err) => eprintln!("{}", err.display_pretty()),
+                None => eprintln!("error: {}", err),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let indexer = Indexer::with_rules(&cli.rules, cli.builtins, &cli.builtins_stub)?;
+    let database = Index::new(&cli.database);
+    match cli.command {
+        Command::Analyze(args) => analyze(indexer, args),
+        Command::Definition(args) => definition(indexer, &database, args),
+        Command::References(args) => references(indexer, args),
+        Command::Trace(args) => trace(indexer, args),
+        Command::Index(args) => index(&indexer, &database, args),
+        Command::Status(args) => status(&database, args),
+        Command::Clean(args) => clean(&database, args),
+        Command::Convert(args) => convert(args),
+        Command::Test(args) => test(&indexer, args),
+        Command::Unresolved(args) => unresolved(indexer, &database, args),
+        Command::Stats(args) => stats(indexer, args),
+        Command::Lsp => {
+            indexer.serve_lsp()?;
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+fn analyze(indexer: Indexer, args: AnalyzeArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    // Ctrl-C を確認するのは analyze と index だけなので、他のコマンドは既定の動作で終了させる
+    install_interrupt_handler()?;
+    let options = BuildOptions {
+        keep_going: args.keep_going,
+        timeout: args.timeout.map(Duration::from_secs),
+        tolerant: args.tolerant,
+    };
+    let analysis = indexer.with_options(options).build(&args.paths)?;
+
+    // ファイルごとの結果を出力
+    for report in &analysis.reports {
+        if let FileOutcome::Built {
+            nodes,
+            syntax_errors,
+        } = &report.outcome
+        {
+            if syntax_errors.is_empty() {
+                println!("{}: {} nodes", report.path.display(), nodes);
+            } else {
+                println!(
+                    "{}: {} nodes, {} syntax errors skipped",
+                    report.path.display(),
+                    nodes,
+                    syntax_errors.len()
+                );
+            }
+        }
+    }
+    let failures = print_failures(&analysis.reports);
+    println!(
+        "Analyzed {} of {} files, {} failed; graph has {} nodes",
+        analysis.reports.len() - failures,
+        analysis.files.len(),
+        failures,
+        analysis.graph.iter_nodes().count()
+    );
+
+    let format = if args.dot {
+        Some(ExportFormat::Dot)
+    } else if args.mermaid {
+        Some(ExportFormat::Mermaid)
+    } else if args.graphml {
+        Some(ExportFormat::Graphml)
+    } else {
+        None
+    };
+    if let Some(format) = format {
+        // 指定された形式でグラフを出力
+        let options = export_options(&Query::new(&analysis.graph), &args)?;
+        let exporter = Exporter::new(&analysis.graph).with_options(options);
+        println!("\n{}", exporter.render(format));
+    }
+
+    let exporter = Exporter::new(&analysis.graph);
+    if let Some(path) = &args.json {
+        // JSON形式でグラフを書き出す
+        fs::write(path, exporter.to_json()).map_err(|err| Error::io(path, err))?;
+    }
+
+    if let Some(path) = &args.html {
+        // ブラウザで開ける HTML を書き出す
+        fs::write(path, exporter.to_html()).map_err(|err| Error::io(path, err))?;
+    }
+
+    if interrupted() {
+        eprintln!("Interrupted");
+        return Ok(ExitCode::FAILURE);
+    }
+    if failures > 0 {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// グラフ出力のフィルタをコマンドライン引数から組み立てる
+fn export_options(
+    query: &Query,
+    args: &AnalyzeArgs,
+) -> Result<ExportOptions, Box<dyn std::error::Error>> {
+    let focus = match &args.dot_focus {
+        Some(position) => {
+            let node = query.node_at(position)?.ok_or_else(|| {
+                format!("{}: no reference or definition at this position", position)
+            })?;
+            Some(node)
+        }
+        None => None,
+    };
+    Ok(ExportOptions {
+        files: args
+            .dot_file
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect(),
+        hide_scopes: args.dot_hide_scopes,
+        focus,
+        depth: args.dot_depth,
+    })
+}
+
+fn definition(
+    indexer: Indexer,
+    database: &Index,
+    args: DefinitionArgs,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let position = &args.target.position;
+    let resolution = if args.use_index {
+        database.resolve(position)?
+    } else {
+        let analysis = build_for_position(indexer, &args.target)?;
+        Query::new(&analysis.graph).definitions(position)?
+    };
+    let resolution =
+        resolution.ok_or_else(|| format!("{}: no reference at this position", position))?;
+
+    if resolution.definitions.is_empty() {
+        println!("`{}` has no definitions", resolution.symbol);
+        return Ok(ExitCode::FAILURE);
+    }
+    for location in &resolution.definitions {
+        println!("{}: definition of `{}`", location, resolution.symbol);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn references(
+    indexer: Indexer,
+    args: PositionArgs,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let analysis = build_for_position(indexer, &args)?;
+    let position = &args.position;
+    let query = Query::new(&analysis.graph);
+    let references = query
+        .references(position)?
+        .ok_or_else(|| format!("{}: no definition at this position", position))?;
+
+    let groups = query.reference_sites(&references);
+    for (file, sites) in &groups {
+        println!("{}", file);
+        for site in sites {
+            println!(
+                "  {}:{}: {}",
+                site.location.start_line, site.location.start_column, site.excerpt
+            );
+        }
+    }
+    println!(
+        "{} references to `{}`",
+        references.nodes.len(),
+        references.symbol
+    );
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn index(
+    indexer: &Indexer,
+    database: &Index,
+    args: IndexArgs,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    install_interrupt_handler()?;
+    let options = IndexOptions {
+        keep_going: args.keep_going,
+        force: args.force,
+        timeout: args.timeout.map(Duration::from_secs),
+        tolerant: args.tolerant,
+    };
+    let summary = indexer.index(database, &args.paths, options)?;
+    for (label, paths) in [
+        ("added", &summary.added),
+        ("updated", &summary.updated),
+        ("removed", &summary.removed),
+    ] {
+        for path in paths {
+            println!("{}: {}", path.display(), label);
+        }
+    }
+    print_failures(&summary.partial);
+    let failures = print_failures(&summary.failed);
+    println!(
+        "{} added, {} updated, {} removed, {} unchanged, {} failed",
+        summary.added.len(),
+        summary.updated.len(),
+        summary.removed.len(),
+        summary.unchanged.len(),
+        failures
+    );
+
+    if interrupted() {
+        eprintln!("Interrupted");
+        return Ok(ExitCode::FAILURE);
+    }
+    if failures > 0 {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn status(database: &Index, args: IndexedPathsArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let entries = database.status(&args.paths)?;
+    for entry in &entries {
+        match &entry.status {
+            FileStatus::Indexed => println!("{}: indexed ({})", entry.path.display(), entry.tag),
+            FileStatus::Error(error) => println!("{}: error: {}", entry.path.display(), error),
+            FileStatus::Missing => println!("{}: missing", entry.path.display()),
+        }
+    }
+    println!("{} files in {}", entries.len(), database.path().display());
+    Ok(ExitCode::SUCCESS)
+}
+
+fn clean(database: &Index, args: IndexedPathsArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let removed = database.clean(&args.paths)?;
+    println!("Removed {} files from {}", removed, database.path().display());
+    Ok(ExitCode::SUCCESS)
+}
+
+fn trace(indexer: Indexer, args: TraceArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let analysis = build_for_position(indexer, &args.target)?;
+    let position = &args.target.position;
+    let trace = Query::new(&analysis.graph)
+        .trace(position)?
+        .ok_or_else(|| format!("{}: no reference at this position", position))?;
+
+    if args.json {
+        println!("{}", trace.to_json(&analysis.graph));
+    } else {
+        print!("{}", trace.to_dot(&analysis.graph));
+    }
+    if trace.truncated {
+        eprintln!("Trace stopped after exploring too many paths");
+    }
+
+    if trace.definitions.is_empty() {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn test(indexer: &Indexer, args: TestArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let reports = indexer.check_fixtures(&args.paths)?;
+
+    let mut assertions = 0;
+    let mut failures = 0;
+    for report in &reports {
+        for failure in &report.failures {
+            eprintln!("{}", failure);
+        }
+        assertions += report.assertions;
+        failures += report.failures.len();
+        let status = if report.failures.is_empty() {
+            "ok"
+        } else {
+            "FAILED"
+        };
+        println!(
+            "{}: {} ({} assertions)",
+            report.file.display(),
+            status,
+            report.assertions
+        );
+    }
+    println!(
+        "Checked {} assertions in {} files, {} failures",
+        assertions,
+        reports.len(),
+        failures
+    );
+
+    if failures > 0 {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn unresolved(
+    indexer: Indexer,
+    database: &Index,
+    args: UnresolvedArgs,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let (report, failures) = if args.use_index {
+        (database.unresolved(&args.paths)?, 0)
+    } else {
+        let options = BuildOptions {
+            keep_going: true,
+            ..BuildOptions::default()
+        };
+        let analysis = indexer.with_options(options).build(&args.paths)?;
+        let failures = print_failures(&analysis.reports);
+
+        // 構築できたファイルの参照だけを調べる
+        let report = Query::new(&analysis.graph).unresolved(&analysis.built_files())?;
+        (report, failures)
+    };
+
+    if args.json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report.to_text());
+    }
+
+    if report.unresolved() > 0 || failures > 0 {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn stats(indexer: Indexer, args: StatsArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let options = BuildOptions {
+        keep_going: true,
+        ..BuildOptions::default()
+    };
+    let analysis = indexer.with_options(options).build(&args.paths)?;
+    let failures = print_failures(&analysis.reports);
+
+    let stats = GraphStats::new(&analysis.graph, args.top);
+    if args.json {
+        println!("{}", stats.to_json());
+    } else {
+        print!("{}", stats.to_text());
+    }
+
+    if failures > 0 {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn convert(args: ConvertArgs) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let json = fs::read_to_string(&args.input).map_err(|err| Error::io(&args.input, err))?;
+    let stack_graph = graph_from_json(&json)
+        .map_err(|err| format!("{}: {}", args.input.display(), err))?;
+    print!("{}", Exporter::new(&stack_graph).render(args.to));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// 位置のファイルと追加のパスからグラフを構築する。失敗したファイルは報告して続ける
+fn build_for_position(
+    indexer: Indexer,
+    args: &PositionArgs,
+) -> Result<Analysis, Box<dyn std::error::Error>> {
+    let mut paths = args.paths.clone();
+    paths.push(args.position.path.clone());
+    let options = BuildOptions {
+        keep_going: true,
+        ..BuildOptions::default()
+    };
+    let analysis = indexer.with_options(options).build(&paths)?;
+    print_failures(&analysis.reports);
+    Ok(analysis)
+}
+
+/// 失敗したファイルを標準エラーに出力し、その数を返す。除外した構文エラーは警告として出力する
+fn print_failures(reports: &[FileReport]) -> usize {
+    let mut failures = 0;
+    for report in reports {
+        match &report.outcome {
+            FileOutcome::Built { syntax_errors, .. } => {
+                for error in syntax_errors {
+                    eprintln!("{}: warning: {}", report.path.display(), error);
+                    eprintln!("{}", error.excerpt);
+                }
+                continue;
+            }
+            FileOutcome::Failed { error } => eprintln!("{}", error.display_pretty()),
+            FileOutcome::TimedOut => eprintln!("{}: timed out", report.path.display()),
+        }
+        failures += 1;
+    }
+    failures
 }
@@ -0,0 +1,489 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{File, Node, StackGraph};
+use stack_graphs::partial::{PartialPath, PartialPaths};
+use stack_graphs::stitching::{ForwardPartialPathStitcher, GraphEdgeCandidates, StitcherConfig};
+use stack_graphs::{CancellationError, NoCancellation};
+
+use crate::error::Error;
+use crate::files;
+use crate::trace::{self, Trace};
+use crate::unresolved::{self, UnresolvedReport};
+
+/// A 1-based `FILE:LINE:COLUMN` position as given on the command line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // ファイル名に ':' が含まれても良いように右から分割する
+        let mut parts = s.rsplitn(3, ':');
+        let (Some(column), Some(line), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("expected FILE:LINE:COLUMN, got `{}`", s));
+        };
+        let line = parse_one_based(line, "line")?;
+        let column = parse_one_based(column, "column")?;
+        Ok(Position {
+            path: PathBuf::from(path),
+            line,
+            column,
+        })
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.path.display(), self.line, self.column)
+    }
+}
+
+fn parse_one_based(value: &str, what: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!(
+            "invalid {} `{}`, expected a number >= 1",
+            what, value
+        )),
+    }
+}
+
+/// The 1-based source range of a node in a file
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub file: String,
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Location {
+    /// Returns the location of a node, if it belongs to a file and has a source span
+    pub fn of_node(graph: &StackGraph, node: Handle<Node>) -> Option<Location> {
+        let file = graph[node].id().file()?;
+        let span = &graph.source_info(node)?.span;
+        Some(Location {
+            file: graph[file].name().to_string(),
+            start_line: span.start.line + 1,
+            start_column: span.start.column.utf8_offset + 1,
+            end_line: span.end.line + 1,
+            end_column: span.end.column.utf8_offset + 1,
+        })
+    }
+
+    /// Returns whether the span is empty, as for nodes without a source node
+    pub fn is_empty(&self) -> bool {
+        (self.start_line, self.start_column) == (self.end_line, self.end_column)
+    }
+
+    fn contains(&self, line: usize, column: usize) -> bool {
+        (self.start_line, self.start_column) <= (line, column)
+            && (line, column) < (self.end_line, self.end_column)
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}-{}:{}",
+            self.file, self.start_line, self.start_column, self.end_line, self.end_column
+        )
+    }
+}
+
+/// Queries over a built graph, addressed by positions in its files
+///
+/// Positions name files as they were named when the graph was built; a file
+/// that is not part of the graph is reported as [`Error::NotIndexed`].
+pub struct Query<'a> {
+    graph: &'a StackGraph,
+}
+
+/// The references that resolve to a definition
+pub struct References {
+    pub symbol: String,
+    /// The distinct reference nodes, sorted by handle
+    pub nodes: Vec<Handle<Node>>,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(graph: &'a StackGraph) -> Self {
+        Query { graph }
+    }
+
+    /// Returns the graph the queries run against
+    pub fn graph(&self) -> &'a StackGraph {
+        self.graph
+    }
+
+    /// Returns the file of a path, if it is part of the graph
+    pub fn file(&self, path: &Path) -> Result<Handle<File>, Error> {
+        self.graph
+            .get_file(&files::normalize(path).to_string_lossy())
+            .ok_or_else(|| Error::NotIndexed(path.to_path_buf()))
+    }
+
+    /// Finds the reference at a position, or else the definition at it
+    pub fn node_at(&self, position: &Position) -> Result<Option<Handle<Node>>, Error> {
+        let file = self.file(&position.path)?;
+        Ok(
+            reference_at(self.graph, file, position.line, position.column)
+                .or_else(|| definition_at(self.graph, file, position.line, position.column)),
+        )
+    }
+
+    /// Resolves the reference at a position to the locations of its definitions
+    ///
+    /// # Returns
+    /// `None` if there is no reference at the position
+    pub fn definitions(&self, position: &Position) -> Result<Option<Resolution>, Error> {
+        let file = self.file(&position.path)?;
+        Ok(resolve_at(
+            self.graph,
+            file,
+            position.line,
+            position.column,
+        )?)
+    }
+
+    /// Finds the references to the definition at a position
+    ///
+    /// If there is a reference rather than a definition at the position, the
+    /// references to every definition it resolves to are returned.
+    ///
+    /// # Returns
+    /// `None` if there is no definition at the position
+    pub fn references(&self, position: &Position) -> Result<Option<References>, Error> {
+        let file = self.file(&position.path)?;
+        let (line, column) = (position.line, position.column);
+        let definitions = match definition_at(self.graph, file, line, column) {
+            Some(definition) => vec![definition],
+            None => match reference_at(self.graph, file, line, column) {
+                Some(reference) => find_definitions(self.graph, reference)?,
+                None => Vec::new(),
+            },
+        };
+        let Some(&first) = definitions.first() else {
+            return Ok(None);
+        };
+
+        let mut nodes = Vec::new();
+        for definition in definitions {
+            nodes.extend(find_references(self.graph, definition)?);
+        }
+        nodes.sort();
+        nodes.dedup();
+        Ok(Some(References {
+            symbol: node_symbol(self.graph, first)
+                .unwrap_or_default()
+                .to_string(),
+            nodes,
+        }))
+    }
+
+    /// Groups the nodes of [`References`] by file, with the source line of each
+    ///
+    /// Source excerpts are read from the files named in the graph; a reference
+    /// whose file cannot be read gets an empty excerpt.
+    ///
+    /// # Returns
+    /// The references by file name, sorted by position within each file
+    pub fn reference_sites(&self, references: &References) -> BTreeMap<String, Vec<ReferenceSite>> {
+        references_by_file(self.graph, &references.nodes)
+    }
+
+    /// Lists the references of the given files that resolve to no definition
+    pub fn unresolved(&self, paths: &[PathBuf]) -> Result<UnresolvedReport, Error> {
+        let files = paths
+            .iter()
+            .map(|path| self.file(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(unresolved::check_files(self.graph, &files)?)
+    }
+
+    /// Records every path explored while resolving the reference at a position
+    ///
+    /// # Returns
+    /// `None` if there is no reference at the position
+    pub fn trace(&self, position: &Position) -> Result<Option<Trace>, Error> {
+        let file = self.file(&position.path)?;
+        Ok(
+            reference_at(self.graph, file, position.line, position.column)
+                .map(|reference| trace::trace_reference(self.graph, reference)),
+        )
+    }
+}
+
+/// Finds the reference node whose source span covers the given position
+///
+/// # Arguments
+/// * `graph` - The graph containing the file
+/// * `file` - The file the position refers to
+/// * `line` - 1-based line
+/// * `column` - 1-based column (UTF-8 byte offset within the line)
+///
+/// # Returns
+/// The reference with the smallest covering span, if any
+pub fn reference_at(
+    graph: &StackGraph,
+    file: Handle<File>,
+    line: usize,
+    column: usize,
+) -> Option<Handle<Node>> {
+    node_at(graph, file, line, column, |node| node.is_reference())
+}
+
+/// Finds the definition node whose source span covers the given position
+///
+/// Takes the same arguments as [`reference_at`].
+pub fn definition_at(
+    graph: &StackGraph,
+    file: Handle<File>,
+    line: usize,
+    column: usize,
+) -> Option<Handle<Node>> {
+    node_at(graph, file, line, column, |node| node.is_definition())
+}
+
+/// 位置を含む最小のスパンを持つノードを探す
+fn node_at(
+    graph: &StackGraph,
+    file: Handle<File>,
+    line: usize,
+    column: usize,
+    predicate: impl Fn(&Node) -> bool,
+) -> Option<Handle<Node>> {
+    graph
+        .nodes_for_file(file)
+        .filter(|&node| predicate(&graph[node]))
+        .filter_map(|node| Some((node, Location::of_node(graph, node)?)))
+        .filter(|(_, location)| location.contains(line, column))
+        .min_by_key(|(_, location)| {
+            (
+                location.end_line - location.start_line,
+                location.end_column.saturating_sub(location.start_column),
+            )
+        })
+        .map(|(node, _)| node)
+}
+
+/// The definitions a reference resolves to
+pub struct Resolution {
+    pub symbol: String,
+    pub definitions: Vec<Location>,
+}
+
+/// Resolves the reference at a position to the locations of its definitions
+///
+/// Takes the same arguments as [`reference_at`].
+///
+/// # Returns
+/// `None` if there is no reference at the position
+pub fn resolve_at(
+    graph: &StackGraph,
+    file: Handle<File>,
+    line: usize,
+    column: usize,
+) -> Result<Option<Resolution>, CancellationError> {
+    let Some(reference) = reference_at(graph, file, line, column) else {
+        return Ok(None);
+    };
+
+    let mut definitions = find_definitions(graph, reference)?
+        .into_iter()
+        .filter_map(|node| Location::of_node(graph, node))
+        .collect::<Vec<_>>();
+    definitions.sort();
+    definitions.dedup();
+
+    Ok(Some(Resolution {
+        symbol: node_symbol(graph, reference)
+            .unwrap_or_default()
+            .to_string(),
+        definitions,
+    }))
+}
+
+/// Resolves a reference node to every definition it has a complete path to
+///
+/// # Arguments
+/// * `graph` - The graph the reference belongs to
+/// * `reference` - The reference node to resolve
+///
+/// # Returns
+/// The distinct definition nodes, in the order they were found
+pub fn find_definitions(
+    graph: &StackGraph,
+    reference: Handle<Node>,
+) -> Result<Vec<Handle<Node>>, CancellationError> {
+    let mut definitions = Vec::new();
+    stitch_complete_paths(graph, vec![reference], |_, definition| {
+        if !definitions.contains(&definition) {
+            definitions.push(definition);
+        }
+    })?;
+    Ok(definitions)
+}
+
+/// Finds every reference in the graph that resolves to the given definition
+///
+/// # Arguments
+/// * `graph` - The graph containing the definition and the indexed files
+/// * `definition` - The definition node to look up
+///
+/// # Returns
+/// The distinct reference nodes, sorted by handle
+pub fn find_references(
+    graph: &StackGraph,
+    definition: Handle<Node>,
+) -> Result<Vec<Handle<Node>>, CancellationError> {
+    let references = graph
+        .iter_nodes()
+        .filter(|&node| graph[node].is_reference())
+        .collect::<Vec<_>>();
+
+    let mut found = BTreeSet::new();
+    stitch_complete_paths(graph, references, |reference, end| {
+        if end == definition {
+            found.insert(reference);
+        }
+    })?;
+    Ok(found.into_iter().collect())
+}
+
+/// Finds the references that have no complete path to any definition
+///
+/// # Arguments
+/// * `graph` - The graph containing the references and the indexed files
+/// * `references` - The reference nodes to resolve
+///
+/// # Returns
+/// The unresolved references, in the given order
+pub fn find_unresolved(
+    graph: &StackGraph,
+    references: Vec<Handle<Node>>,
+) -> Result<Vec<Handle<Node>>, CancellationError> {
+    let mut resolved = HashSet::new();
+    stitch_complete_paths(graph, references.clone(), |reference, _| {
+        resolved.insert(reference);
+    })?;
+    Ok(references
+        .into_iter()
+        .filter(|reference| !resolved.contains(reference))
+        .collect())
+}
+
+/// 参照ノードから完全なパスを探索し、隠されていないパスの (始点, 終点) を visit に渡す
+fn stitch_complete_paths<F>(
+    graph: &StackGraph,
+    references: Vec<Handle<Node>>,
+    mut visit: F,
+) -> Result<(), CancellationError>
+where
+    F: FnMut(Handle<Node>, Handle<Node>),
+{
+    let mut partials = PartialPaths::new();
+    let mut paths = Vec::new();
+    ForwardPartialPathStitcher::find_all_complete_partial_paths(
+        &mut GraphEdgeCandidates::new(graph, &mut partials, None),
+        references,
+        StitcherConfig::default(),
+        &NoCancellation,
+        |_graph, _partials, path| paths.push(path.clone()),
+    )?;
+    for path in remove_shadowed(&mut partials, paths) {
+        visit(path.start_node, path.end_node);
+    }
+    Ok(())
+}
+
+/// Drops the paths that are shadowed by another path from the same reference
+///
+/// A path shadows another when both leave the same node through edges of
+/// different precedence and it took the higher one, e.g. a module's own
+/// definition shadows the builtin of the same name.
+///
+/// # Returns
+/// The remaining paths, in the given order
+pub(crate) fn remove_shadowed(
+    partials: &mut PartialPaths,
+    paths: Vec<PartialPath>,
+) -> Vec<PartialPath> {
+    let mut by_reference: HashMap<Handle<Node>, Vec<PartialPath>> = HashMap::new();
+    for path in &paths {
+        by_reference
+            .entry(path.start_node)
+            .or_default()
+            .push(path.clone());
+    }
+    paths
+        .into_iter()
+        .filter(|path| {
+            by_reference[&path.start_node]
+                .iter()
+                .all(|other| !other.shadows(partials, path))
+        })
+        .collect()
+}
+
+/// A reference together with the source line it appears on
+pub struct ReferenceSite {
+    pub location: Location,
+    pub excerpt: String,
+}
+
+/// Groups reference nodes by file, sorted by position within each file
+///
+/// Source excerpts are read from the files named in the graph; a reference whose
+/// file cannot be read gets an empty excerpt.
+pub fn references_by_file(
+    graph: &StackGraph,
+    references: &[Handle<Node>],
+) -> BTreeMap<String, Vec<ReferenceSite>> {
+    let mut lines_by_file: HashMap<String, Vec<String>> = HashMap::new();
+    let mut groups: BTreeMap<String, Vec<ReferenceSite>> = BTreeMap::new();
+
+    for &reference in references {
+        let Some(location) = Location::of_node(graph, reference) else {
+            continue;
+        };
+        let lines = lines_by_file
+            .entry(location.file.clone())
+            .or_insert_with(|| {
+                fs::read_to_string(&location.file)
+                    .map(|source| source.lines().map(str::to_string).collect())
+                    .unwrap_or_default()
+            });
+        let excerpt = lines
+            .get(location.start_line - 1)
+            .map(|line| line.trim().to_string())
+            .unwrap_or_default();
+        groups
+            .entry(location.file.clone())
+            .or_default()
+            .push(ReferenceSite { location, excerpt });
+    }
+
+    for sites in groups.values_mut() {
+        sites.sort_by(|a, b| a.location.cmp(&b.location));
+    }
+    groups
+}
+
+/// Returns the symbol text of a push or pop node
+pub fn node_symbol(graph: &StackGraph, node: Handle<Node>) -> Option<&str> {
+    graph[node].symbol().map(|symbol| &graph[symbol])
+}
@@ -0,0 +1,253 @@
+//! Languages known to the tool and their TSG rules
+//!
+//! Every language pairs a tree-sitter grammar with a TSG ruleset and the file
+//! extensions it is used for. The built-in rules live next to this module, one
+//! `.tsg` file per language, and can be replaced per language on the command
+//! line.
+//!
+//! A language may also come with a builtins stub, see [`crate::builtins`], whose
+//! graph fragment is added before any source file when builtins are enabled.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use stack_graphs::graph::StackGraph;
+use tree_sitter_stack_graphs::StackGraphLanguage;
+
+use crate::builtins::Stub;
+use crate::error::Error;
+
+/// A language with built-in rules
+struct Builtin {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    grammar: fn() -> tree_sitter::Language,
+    rules: &'static str,
+    /// Builtins stub, for languages whose rules look names up in the builtins
+    builtins: Option<&'static str>,
+}
+
+/// Built-in languages, in the order they are listed
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "python",
+        extensions: &["py"],
+        grammar: python_grammar,
+        rules: include_str!("python.tsg"),
+        builtins: Some(include_str!("python.builtins")),
+    },
+    Builtin {
+        name: "javascript",
+        extensions: &["js", "mjs", "cjs"],
+        grammar: javascript_grammar,
+        rules: include_str!("javascript.tsg"),
+        builtins: None,
+    },
+];
+
+fn python_grammar() -> tree_sitter::Language {
+    tree_sitter_python::LANGUAGE.into()
+}
+
+fn javascript_grammar() -> tree_sitter::Language {
+    tree_sitter_javascript::LANGUAGE.into()
+}
+
+/// A `[LANGUAGE=]FILE` argument replacing a built-in file of one language,
+/// as given to `--rules` and `--builtins-stub`
+#[derive(Clone, Debug)]
+pub struct LanguageFile {
+    /// Name of the language, e.g. `python`
+    pub language: String,
+    /// Path of the file to use instead
+    pub path: PathBuf,
+}
+
+impl FromStr for LanguageFile {
+    type Err = String;
+
+    /// Parses `LANGUAGE=FILE`; a bare `FILE` belongs to Python
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (language, path) = match value.split_once('=') {
+            Some((language, path)) => (language, path),
+            None => ("python", value),
+        };
+        if !BUILTINS.iter().any(|builtin| builtin.name == language) {
+            return Err(format!(
+                "unknown language `{}`, expected one of {}",
+                language,
+                language_names().join(", ")
+            ));
+        }
+        Ok(LanguageFile {
+            language: language.to_string(),
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+/// A language whose rules have been compiled
+pub struct Language {
+    pub name: &'static str,
+    /// File extensions without the leading dot
+    pub extensions: &'static [&'static str],
+    pub grammar: tree_sitter::Language,
+    /// Path of the TSG rules, `<builtin>/LANGUAGE.tsg` for the built-in ones
+    pub rules_path: PathBuf,
+    /// Source of the TSG rules, for rendering execution errors
+    pub rules: String,
+    pub stack_graph_language: StackGraphLanguage,
+    /// Builtins stub, if builtins are enabled and the language has them
+    pub builtins: Option<Stub>,
+}
+
+/// The languages used to build stack graphs, looked up by file extension
+pub struct LanguageRegistry {
+    languages: Vec<Language>,
+}
+
+impl LanguageRegistry {
+    /// Returns the language of a file, based on its extension
+    pub fn for_path(&self, path: &Path) -> Option<&Language> {
+        let extension = path.extension()?.to_str()?;
+        self.languages
+            .iter()
+            .find(|language| language.extensions.contains(&extension))
+    }
+
+    /// Returns the extensions of all registered languages
+    pub fn extensions(&self) -> Vec<&'static str> {
+        self.languages
+            .iter()
+            .flat_map(|language| language.extensions.iter().copied())
+            .collect()
+    }
+
+    /// Returns the builtins stubs of all languages that have them
+    pub fn builtins(&self) -> impl Iterator<Item = &Stub> {
+        self.languages
+            .iter()
+            .filter_map(|language| language.builtins.as_ref())
+    }
+
+    /// Adds the fragments of all builtins stubs to a graph
+    ///
+    /// Called on an empty graph before any source file is added, so that the
+    /// stubs' files come first.
+    pub fn add_builtins(&self, graph: &mut StackGraph) {
+        for stub in self.builtins() {
+            stub.add_to_graph(graph);
+        }
+    }
+}
+
+/// 組み込み言語の名前を返す
+fn language_names() -> Vec<&'static str> {
+    BUILTINS.iter().map(|builtin| builtin.name).collect()
+}
+
+/// Compiles the rules of every built-in language
+///
+/// # Arguments
+/// * `rules` - Rules files replacing the built-in rules of some languages; a
+///   later file for the same language wins
+/// * `builtins` - Whether to load the built-in builtins stubs
+/// * `stubs` - Builtins stubs replacing the built-in ones of some languages,
+///   which enable builtins for their language regardless of `builtins`
+///
+/// # Returns
+/// The registry of all languages. Rules that fail to compile are reported as
+/// [`Error::Compile`], whose rendering shows the offending rule.
+pub fn load_languages(
+    rules: &[LanguageFile],
+    builtins: bool,
+    stubs: &[LanguageFile],
+) -> Result<LanguageRegistry, Error> {
+    let mut languages = Vec::new();
+    for builtin in BUILTINS {
+        let rules_path = override_for(rules, builtin);
+        let stub_path = override_for(stubs, builtin);
+        let stub = match (stub_path, builtin.builtins) {
+            (Some(path), Some(_)) => Some(load_stub(builtin, Some(path))?),
+            (Some(_), None) => {
+                return Err(Error::Language(format!(
+                    "{} rules do not look up builtins",
+                    builtin.name
+                )))
+            }
+            (None, Some(_)) if builtins => Some(load_stub(builtin, None)?),
+            (None, _) => None,
+        };
+        languages.push(Language {
+            builtins: stub,
+            ..load_language(builtin, rules_path)?
+        });
+    }
+    Ok(LanguageRegistry { languages })
+}
+
+/// 言語に対する最後の指定ファイルを返す
+fn override_for<'a>(files: &'a [LanguageFile], builtin: &Builtin) -> Option<&'a Path> {
+    files
+        .iter()
+        .rev()
+        .find(|file| file.language == builtin.name)
+        .map(|file| file.path.as_path())
+}
+
+/// 言語1つ分のルールをファイルまたは組み込みのルールからコンパイルする
+fn load_language(builtin: &Builtin, rules_path: Option<&Path>) -> Result<Language, Error> {
+    let (path, source) = match rules_path {
+        Some(path) => {
+            let source = fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+            (path.to_path_buf(), source)
+        }
+        None => (
+            PathBuf::from(format!("<builtin>/{}.tsg", builtin.name)),
+            builtin.rules.to_string(),
+        ),
+    };
+
+    // ABI の合わない文法はルールのコンパイル前に検出する
+    let grammar = (builtin.grammar)();
+    tree_sitter::Parser::new()
+        .set_language(&grammar)
+        .map_err(|err| Error::Grammar {
+            language: builtin.name.to_string(),
+            message: err.to_string(),
+        })?;
+
+    let stack_graph_language =
+        StackGraphLanguage::from_str(grammar.clone(), &source).map_err(|err| Error::Compile {
+            path: path.clone(),
+            message: err.to_string(),
+            rendered: err.display_pretty(&path, &source).to_string(),
+        })?;
+    Ok(Language {
+        name: builtin.name,
+        extensions: builtin.extensions,
+        grammar,
+        rules_path: path,
+        rules: source,
+        stack_graph_language,
+        builtins: None,
+    })
+}
+
+/// 組み込み名のスタブをファイルまたは組み込みのスタブから読み込む
+fn load_stub(builtin: &Builtin, stub_path: Option<&Path>) -> Result<Stub, Error> {
+    let (file_name, source) = match stub_path {
+        Some(path) => {
+            let source = fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+            (path.to_string_lossy().into_owned(), source)
+        }
+        None => (
+            format!("<builtin>/{}.builtins", builtin.name),
+            builtin.builtins.unwrap_or_default().to_string(),
+        ),
+    };
+
+    Stub::parse(&file_name, &source)
+        .map_err(|(line, message)| Error::Language(format!("{}:{}: {}", file_name, line, message)))
+}
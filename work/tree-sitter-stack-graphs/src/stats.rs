@@ -0,0 +1,290 @@
+//! Statistics of a built graph, to spot TSG rules that emit garbage
+//!
+//! Nodes are counted per file and in total by kind (the kinds of
+//! [`node_kind`], which the exporters colour), along with their outgoing edges,
+//! definitions and references. Two counts point at suspicious rules: orphan
+//! nodes, which have no edges at all, and scopes without outgoing edges, from
+//! which no lookup can continue. For a `main.py` containing
+//!
+//! ```python
+//! def helper(x):
+//!     return x
+//!
+//! helper(1)
+//! ```
+//!
+//! `stats --top 2 main.py` prints the report as text ([`GraphStats::to_text`]):
+//!
+//! ```text
+//! main.py
+//!   nodes: 10 (pop_symbol 4, push_symbol 3, scope 3)
+//!   edges: 10
+//!   definitions: 3, references: 2
+//!   orphan nodes: 0, scopes without outgoing edges: 0
+//!   symbols: 5
+//! total
+//!   nodes: 12 (jump_to_scope 1, pop_symbol 4, push_symbol 3, root 1, scope 3)
+//!   edges: 11
+//!   definitions: 3, references: 2
+//!   orphan nodes: 1, scopes without outgoing edges: 0
+//!   symbols: 5
+//! symbol table: 5 symbols
+//! top symbols:
+//!   helper: 2
+//!   x: 2
+//! ```
+//!
+//! and `--json` prints the same information ([`GraphStats::to_json`]):
+//!
+//! ```json
+//! {
+//!   "files": [
+//!     {
+//!       "file": "main.py",
+//!       "nodes": 10,
+//!       "nodes_by_kind": { "pop_symbol": 4, "push_symbol": 3, "scope": 3 },
+//!       "edges": 10,
+//!       "definitions": 3,
+//!       "references": 2,
+//!       "orphan_nodes": 0,
+//!       "dead_end_scopes": 0,
+//!       "symbols": 5
+//!     }
+//!   ],
+//!   "total": { "nodes": 12, "...": "..." },
+//!   "symbol_table": 5,
+//!   "top_symbols": [{ "symbol": "helper", "count": 2 }, { "symbol": "x", "count": 2 }]
+//! }
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::Serialize;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{Node, StackGraph, Symbol};
+
+use crate::canonical::node_kind;
+
+/// Counts of the nodes of one file, or of the whole graph
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Counts {
+    pub nodes: usize,
+    /// Number of nodes of each kind, keyed by `root`, `scope`, `push_symbol`, ...
+    pub nodes_by_kind: BTreeMap<&'static str, usize>,
+    /// Outgoing edges of the nodes
+    pub edges: usize,
+    pub definitions: usize,
+    pub references: usize,
+    /// Nodes without incoming or outgoing edges
+    pub orphan_nodes: usize,
+    /// Scope nodes without outgoing edges
+    pub dead_end_scopes: usize,
+    /// Distinct symbols of the push and pop nodes
+    pub symbols: usize,
+}
+
+/// Statistics of a whole graph
+#[derive(Debug, Default)]
+pub struct GraphStats {
+    /// Counts by file name, including files without nodes
+    pub files: BTreeMap<String, Counts>,
+    /// Counts of all nodes, including the root and jump-to nodes
+    pub total: Counts,
+    /// Number of symbols interned in the graph, used by a node or not
+    pub symbol_table: usize,
+    /// The symbols used by the most push and pop nodes, with their node
+    /// counts, most used first and ties sorted by symbol
+    pub top_symbols: Vec<(String, usize)>,
+}
+
+impl GraphStats {
+    /// Computes the statistics of a graph
+    ///
+    /// # Arguments
+    /// * `graph` - The graph to examine
+    /// * `top` - Number of most common symbols to report
+    pub fn new(graph: &StackGraph, top: usize) -> GraphStats {
+        graph_stats(graph, top)
+    }
+
+    /// Formats the statistics as text, as described in the module documentation
+    pub fn to_text(&self) -> String {
+        to_text(self)
+    }
+
+    /// Formats the statistics as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        to_json(self)
+    }
+}
+
+/// Computes the statistics of a graph
+///
+/// # Arguments
+/// * `graph` - The graph to examine
+/// * `top` - Number of most common symbols to report
+pub fn graph_stats(graph: &StackGraph, top: usize) -> GraphStats {
+    let mut stats = GraphStats {
+        symbol_table: graph.iter_symbols().count(),
+        ..GraphStats::default()
+    };
+    for file in graph.iter_files() {
+        stats
+            .files
+            .entry(graph[file].name().to_string())
+            .or_default();
+    }
+
+    let incoming = graph
+        .iter_nodes()
+        .flat_map(|node| graph.outgoing_edges(node).map(|edge| edge.sink))
+        .collect::<HashSet<_>>();
+
+    let mut file_symbols: BTreeMap<String, HashSet<Handle<Symbol>>> = BTreeMap::new();
+    let mut symbol_nodes: HashMap<Handle<Symbol>, usize> = HashMap::new();
+    for node in graph.iter_nodes() {
+        let data = &graph[node];
+        let outgoing = graph.outgoing_edges(node).count();
+        let has_incoming = incoming.contains(&node);
+
+        count_node(&mut stats.total, data, outgoing, has_incoming);
+        if let Some(file) = data.id().file() {
+            let name = graph[file].name();
+            let counts = stats.files.entry(name.to_string()).or_default();
+            count_node(counts, data, outgoing, has_incoming);
+            if let Some(symbol) = data.symbol() {
+                file_symbols
+                    .entry(name.to_string())
+                    .or_default()
+                    .insert(symbol);
+            }
+        }
+        if let Some(symbol) = data.symbol() {
+            *symbol_nodes.entry(symbol).or_default() += 1;
+        }
+    }
+
+    for (file, symbols) in file_symbols {
+        if let Some(counts) = stats.files.get_mut(&file) {
+            counts.symbols = symbols.len();
+        }
+    }
+    stats.total.symbols = symbol_nodes.len();
+
+    let mut top_symbols = symbol_nodes
+        .into_iter()
+        .map(|(symbol, count)| (graph[symbol].to_string(), count))
+        .collect::<Vec<_>>();
+    top_symbols.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_symbols.truncate(top);
+    stats.top_symbols = top_symbols;
+
+    stats
+}
+
+/// ノード1つ分を集計に加える
+fn count_node(counts: &mut Counts, node: &Node, outgoing: usize, has_incoming: bool) {
+    counts.nodes += 1;
+    *counts.nodes_by_kind.entry(node_kind(node)).or_default() += 1;
+    counts.edges += outgoing;
+    if node.is_definition() {
+        counts.definitions += 1;
+    }
+    if node.is_reference() {
+        counts.references += 1;
+    }
+    if outgoing == 0 && !has_incoming {
+        counts.orphan_nodes += 1;
+    }
+    if outgoing == 0 && matches!(node, Node::Scope(_)) {
+        counts.dead_end_scopes += 1;
+    }
+}
+
+/// Formats the statistics as described in the module documentation
+pub fn to_text(stats: &GraphStats) -> String {
+    let mut text = String::new();
+    for (file, counts) in &stats.files {
+        text.push_str(&format!("{}\n", file));
+        push_counts(&mut text, counts);
+    }
+    text.push_str("total\n");
+    push_counts(&mut text, &stats.total);
+    text.push_str(&format!("symbol table: {} symbols\n", stats.symbol_table));
+    text.push_str("top symbols:\n");
+    for (symbol, count) in &stats.top_symbols {
+        text.push_str(&format!("  {}: {}\n", symbol, count));
+    }
+    text
+}
+
+/// 1ファイル分の集計をインデントして書き出す
+fn push_counts(text: &mut String, counts: &Counts) {
+    let kinds = counts
+        .nodes_by_kind
+        .iter()
+        .map(|(kind, count)| format!("{} {}", kind, count))
+        .collect::<Vec<_>>();
+    if kinds.is_empty() {
+        text.push_str(&format!("  nodes: {}\n", counts.nodes));
+    } else {
+        text.push_str(&format!(
+            "  nodes: {} ({})\n",
+            counts.nodes,
+            kinds.join(", ")
+        ));
+    }
+    text.push_str(&format!("  edges: {}\n", counts.edges));
+    text.push_str(&format!(
+        "  definitions: {}, references: {}\n",
+        counts.definitions, counts.references
+    ));
+    text.push_str(&format!(
+        "  orphan nodes: {}, scopes without outgoing edges: {}\n",
+        counts.orphan_nodes, counts.dead_end_scopes
+    ));
+    text.push_str(&format!("  symbols: {}\n", counts.symbols));
+}
+
+#[derive(Serialize)]
+struct StatsJson<'a> {
+    files: Vec<FileJson<'a>>,
+    total: &'a Counts,
+    symbol_table: usize,
+    top_symbols: Vec<SymbolJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct FileJson<'a> {
+    file: &'a str,
+    #[serde(flatten)]
+    counts: &'a Counts,
+}
+
+#[derive(Serialize)]
+struct SymbolJson<'a> {
+    symbol: &'a str,
+    count: usize,
+}
+
+/// Formats the statistics as JSON as described in the module documentation
+pub fn to_json(stats: &GraphStats) -> String {
+    let json = StatsJson {
+        files: stats
+            .files
+            .iter()
+            .map(|(file, counts)| FileJson { file, counts })
+            .collect(),
+        total: &stats.total,
+        symbol_table: stats.symbol_table,
+        top_symbols: stats
+            .top_symbols
+            .iter()
+            .map(|(symbol, count)| SymbolJson {
+                symbol,
+                count: *count,
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&json).expect("statistics are serializable")
+}
@@ -0,0 +1,425 @@
+//! Path-finding traces for a single reference
+//!
+//! [`trace_reference`] explores the graph from a reference node the way the path
+//! stitcher does, one edge at a time, and records the symbol and scope stacks
+//! after every step as well as every edge that could not be followed. The trace
+//! can be rendered as a DOT overlay on the graph ([`to_dot`]) or as JSON that
+//! refers to nodes by the IDs used in [`json_export`](crate::json_export)
+//! ([`to_json`]).
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use serde::Serialize;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{Node, StackGraph};
+use stack_graphs::partial::{PartialPath, PartialPaths};
+
+use crate::export::dot::{escape, DotBackend};
+use crate::export::{self, ExportOptions};
+use crate::json_export::{self, NodeIdJson};
+use crate::query;
+
+/// Number of paths explored before a trace gives up
+const MAX_TRACE_PATHS: usize = 10_000;
+
+/// Everything explored while resolving one reference
+pub struct Trace {
+    pub reference: Handle<Node>,
+    /// Edges that extended a path, in the order they were explored
+    pub steps: Vec<TraceStep>,
+    /// Places where a path could not be extended
+    pub stops: Vec<TraceStop>,
+    /// Definitions reached by complete paths
+    pub definitions: Vec<Handle<Node>>,
+    /// Whether exploration ended early after 10,000 paths
+    pub truncated: bool,
+}
+
+impl Trace {
+    /// Renders the trace as a DOT overlay on the graph of the files it passed
+    /// through, as described in the module documentation
+    pub fn to_dot(&self, graph: &StackGraph) -> String {
+        to_dot(graph, self)
+    }
+
+    /// Renders the trace as pretty-printed JSON, with nodes identified as in
+    /// the JSON export
+    pub fn to_json(&self, graph: &StackGraph) -> String {
+        to_json(graph, self)
+    }
+}
+
+/// One edge followed by a path, with the stacks after following it
+pub struct TraceStep {
+    pub source: Handle<Node>,
+    pub sink: Handle<Node>,
+    pub precedence: i32,
+    /// Symbol stack, top first
+    pub symbol_stack: Vec<String>,
+    /// Scope stack, top first
+    pub scope_stack: Vec<String>,
+}
+
+/// A path that could not be extended
+pub struct TraceStop {
+    /// The node the path ended at
+    pub node: Handle<Node>,
+    /// The node the path failed to move to, `None` if there was no edge to follow
+    pub sink: Option<Handle<Node>>,
+    /// Symbol stack at `node`, top first
+    pub symbol_stack: Vec<String>,
+    /// Scope stack at `node`, top first
+    pub scope_stack: Vec<String>,
+    pub reason: String,
+}
+
+/// Explores every path from a reference and records what happened on the way
+///
+/// Paths are extended breadth-first. A path that reaches a node with the same
+/// stacks as an earlier path is not extended again, so cycles in the graph end
+/// the exploration instead of looping. Definitions reached only by paths that
+/// are shadowed by another path, see [`query::remove_shadowed`], are left out,
+/// as they are when resolving the reference.
+///
+/// # Arguments
+/// * `graph` - The graph the reference belongs to
+/// * `reference` - The reference node to trace
+///
+/// # Returns
+/// The explored steps, the stops and the definitions that were reached
+pub fn trace_reference(graph: &StackGraph, reference: Handle<Node>) -> Trace {
+    let mut partials = PartialPaths::new();
+    let mut trace = Trace {
+        reference,
+        steps: Vec::new(),
+        stops: Vec::new(),
+        definitions: Vec::new(),
+        truncated: false,
+    };
+
+    // 完全なパスだけを探すので、事前条件のスタック変数は取り除く
+    let mut start = PartialPath::from_node(graph, &mut partials, reference);
+    start.eliminate_precondition_stack_variables(&mut partials);
+
+    let mut seen = HashSet::new();
+    let mut complete = Vec::new();
+    let mut queue = VecDeque::from([start]);
+    let mut explored = 0;
+    while let Some(mut path) = queue.pop_front() {
+        explored += 1;
+        if explored > MAX_TRACE_PATHS {
+            trace.truncated = true;
+            break;
+        }
+
+        if path.end_node == StackGraph::jump_to_node() {
+            // ジャンプ先はスコープスタックの先頭から決まる
+            let symbols = symbol_stack(graph, &mut partials, &path);
+            let scopes = scope_stack(graph, &mut partials, &path);
+            if let Err(err) = path.resolve_from_postcondition(graph, &mut partials) {
+                trace.stops.push(TraceStop {
+                    node: path.end_node,
+                    sink: None,
+                    symbol_stack: symbols,
+                    scope_stack: scopes,
+                    reason: format!("{:?}", err),
+                });
+                continue;
+            }
+        }
+
+        let symbols = symbol_stack(graph, &mut partials, &path);
+        let scopes = scope_stack(graph, &mut partials, &path);
+        if !seen.insert((path.end_node, symbols.clone(), scopes.clone())) {
+            continue;
+        }
+        if path.is_complete(graph) {
+            complete.push(path);
+            continue;
+        }
+
+        for edge in graph.outgoing_edges(path.end_node) {
+            let mut next = path.clone();
+            match next.append(graph, &mut partials, edge) {
+                Ok(()) => {
+                    trace.steps.push(TraceStep {
+                        source: edge.source,
+                        sink: edge.sink,
+                        precedence: edge.precedence,
+                        symbol_stack: symbol_stack(graph, &mut partials, &next),
+                        scope_stack: scope_stack(graph, &mut partials, &next),
+                    });
+                    queue.push_back(next);
+                }
+                Err(err) => trace.stops.push(TraceStop {
+                    node: path.end_node,
+                    sink: Some(edge.sink),
+                    symbol_stack: symbols.clone(),
+                    scope_stack: scopes.clone(),
+                    reason: stop_reason(graph, edge.sink, format!("{:?}", err)),
+                }),
+            }
+        }
+        if graph.outgoing_edges(path.end_node).next().is_none() {
+            trace.stops.push(TraceStop {
+                node: path.end_node,
+                sink: None,
+                symbol_stack: symbols,
+                scope_stack: scopes,
+                reason: "no outgoing edges".to_string(),
+            });
+        }
+    }
+
+    // 優先度の高いパスに隠された定義は報告しない
+    for path in query::remove_shadowed(&mut partials, complete) {
+        if !trace.definitions.contains(&path.end_node) {
+            trace.definitions.push(path.end_node);
+        }
+    }
+    trace
+}
+
+/// シンボルスタックを先頭から文字列にする
+fn symbol_stack(
+    graph: &StackGraph,
+    partials: &mut PartialPaths,
+    path: &PartialPath,
+) -> Vec<String> {
+    path.symbol_stack_postcondition
+        .iter(partials)
+        .map(|symbol| graph[symbol.symbol].to_string())
+        .collect()
+}
+
+/// スコープスタックを先頭から文字列にする
+fn scope_stack(graph: &StackGraph, partials: &mut PartialPaths, path: &PartialPath) -> Vec<String> {
+    path.scope_stack_postcondition
+        .iter_scopes(partials)
+        .map(|scope| scope_name(graph, scope))
+        .collect()
+}
+
+/// スコープを `ファイル:ローカルID` の形で表す
+fn scope_name(graph: &StackGraph, scope: Handle<Node>) -> String {
+    let id = graph[scope].id();
+    match id.file() {
+        Some(file) => format!("{}:{}", graph[file].name(), id.local_id()),
+        None => export::node_kind(&graph[scope]).to_string(),
+    }
+}
+
+/// pop ノードで止まった場合は期待していたシンボルを添える
+fn stop_reason(graph: &StackGraph, sink: Handle<Node>, error: String) -> String {
+    match &graph[sink] {
+        Node::PopSymbol(_) | Node::PopScopedSymbol(_) => format!(
+            "{}: `{}` is not on top of the symbol stack",
+            error,
+            query::node_symbol(graph, sink).unwrap_or_default()
+        ),
+        _ => error,
+    }
+}
+
+/// Renders a trace as DOT, overlaid on the files it passed through
+///
+/// Explored edges are drawn blue and labelled with their step number and the
+/// stacks after the step; edges that could not be followed are drawn red and
+/// dashed, labelled with the reason. An edge explored or refused more than once
+/// gets one label line group per time, in the order they happened. The
+/// reference is outlined blue, reached definitions green and nodes where a path
+/// stopped red.
+///
+/// # Arguments
+/// * `graph` - The graph the trace was recorded on
+/// * `trace` - The trace to render
+///
+/// # Returns
+/// A String containing the DOT format representation of the overlay
+pub fn to_dot(graph: &StackGraph, trace: &Trace) -> String {
+    let mut overlay = Vec::new();
+    let id = export::node_id;
+
+    overlay.push(format!(
+        "\"{}\" [color=\"blue\", penwidth=3];",
+        id(trace.reference)
+    ));
+    // 辺ごとにラベルをまとめ、strict digraph で元の辺と重なるよう1文にする
+    let mut edges = EdgeOverlays::default();
+    for (index, step) in trace.steps.iter().enumerate() {
+        let edge = edges.get(step.source, step.sink);
+        edge.followed = true;
+        edge.labels.push(format!(
+            "{}: {}\\nsymbols: {}\\nscopes: {}",
+            index + 1,
+            step.precedence,
+            escape(&step.symbol_stack.join(" ")),
+            escape(&step.scope_stack.join(" "))
+        ));
+    }
+    let mut stopped = HashSet::new();
+    for stop in &trace.stops {
+        if stopped.insert(stop.node) {
+            overlay.push(format!(
+                "\"{}\" [color=\"red\", penwidth=3];",
+                id(stop.node)
+            ));
+        }
+        if let Some(sink) = stop.sink {
+            edges.get(stop.node, sink).labels.push(format!(
+                "{}\\nsymbols: {}",
+                escape(&stop.reason),
+                escape(&stop.symbol_stack.join(" "))
+            ));
+        }
+    }
+    for edge in &edges.edges {
+        // 一度でも辿れた辺は青、辿れなかっただけの辺は赤の破線にする
+        let style = if edge.followed {
+            "color=\"blue\", fontcolor=\"blue\""
+        } else {
+            "color=\"red\", fontcolor=\"red\", style=dashed"
+        };
+        overlay.push(format!(
+            "\"{}\" -> \"{}\" [{}, label=\"{}\"];",
+            id(edge.source),
+            id(edge.sink),
+            style,
+            edge.labels.join("\\n")
+        ));
+    }
+    for &definition in &trace.definitions {
+        overlay.push(format!(
+            "\"{}\" [color=\"green\", penwidth=3];",
+            id(definition)
+        ));
+    }
+
+    // トレースが通ったファイルだけを出力する
+    let files = touched_nodes(trace)
+        .into_iter()
+        .filter_map(|node| graph[node].id().file())
+        .map(|file| graph[file].name().to_string())
+        .collect::<BTreeSet<_>>();
+    let options = ExportOptions {
+        files: files.into_iter().collect(),
+        ..Default::default()
+    };
+    export::export(graph, &options, DotBackend::with_overlay(overlay))
+}
+
+/// オーバーレイで1本の辺に重ねる内容
+struct EdgeOverlay {
+    source: Handle<Node>,
+    sink: Handle<Node>,
+    /// 一度でも辿れたかどうか
+    followed: bool,
+    labels: Vec<String>,
+}
+
+/// 最初に現れた順に並べた、辺ごとのオーバーレイ
+#[derive(Default)]
+struct EdgeOverlays {
+    edges: Vec<EdgeOverlay>,
+    index: HashMap<(Handle<Node>, Handle<Node>), usize>,
+}
+
+impl EdgeOverlays {
+    /// 辺のオーバーレイを返す。初めての辺なら追加する
+    fn get(&mut self, source: Handle<Node>, sink: Handle<Node>) -> &mut EdgeOverlay {
+        let edges = &mut self.edges;
+        let index = *self.index.entry((source, sink)).or_insert_with(|| {
+            edges.push(EdgeOverlay {
+                source,
+                sink,
+                followed: false,
+                labels: Vec::new(),
+            });
+            edges.len() - 1
+        });
+        &mut self.edges[index]
+    }
+}
+
+fn touched_nodes(trace: &Trace) -> Vec<Handle<Node>> {
+    let mut nodes = vec![trace.reference];
+    nodes.extend(trace.steps.iter().flat_map(|step| [step.source, step.sink]));
+    nodes.extend(trace.stops.iter().flat_map(|stop| stop.sink));
+    nodes.extend(&trace.definitions);
+    nodes
+}
+
+#[derive(Serialize)]
+struct TraceJson {
+    reference: NodeIdJson,
+    symbol: Option<String>,
+    definitions: Vec<NodeIdJson>,
+    steps: Vec<TraceStepJson>,
+    stops: Vec<TraceStopJson>,
+    truncated: bool,
+}
+
+#[derive(Serialize)]
+struct TraceStepJson {
+    source: NodeIdJson,
+    sink: NodeIdJson,
+    precedence: i32,
+    symbol_stack: Vec<String>,
+    scope_stack: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct TraceStopJson {
+    node: NodeIdJson,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sink: Option<NodeIdJson>,
+    symbol_stack: Vec<String>,
+    scope_stack: Vec<String>,
+    reason: String,
+}
+
+/// Renders a trace as JSON
+///
+/// Nodes are identified as in the graph JSON written by
+/// [`json_export::to_json`], so the trace can be laid over an exported graph.
+/// Steps and stops appear in the order they were explored; stacks are listed
+/// top first.
+///
+/// # Arguments
+/// * `graph` - The graph the trace was recorded on
+/// * `trace` - The trace to render
+///
+/// # Returns
+/// A String containing the pretty-printed JSON
+pub fn to_json(graph: &StackGraph, trace: &Trace) -> String {
+    let id = |node: Handle<Node>| json_export::node_id_json(graph, graph[node].id());
+    let json = TraceJson {
+        reference: id(trace.reference),
+        symbol: query::node_symbol(graph, trace.reference).map(str::to_string),
+        definitions: trace.definitions.iter().map(|&node| id(node)).collect(),
+        steps: trace
+            .steps
+            .iter()
+            .map(|step| TraceStepJson {
+                source: id(step.source),
+                sink: id(step.sink),
+                precedence: step.precedence,
+                symbol_stack: step.symbol_stack.clone(),
+                scope_stack: step.scope_stack.clone(),
+            })
+            .collect(),
+        stops: trace
+            .stops
+            .iter()
+            .map(|stop| TraceStopJson {
+                node: id(stop.node),
+                sink: stop.sink.map(id),
+                symbol_stack: stop.symbol_stack.clone(),
+                scope_stack: stop.scope_stack.clone(),
+                reason: stop.reason.clone(),
+            })
+            .collect(),
+        truncated: trace.truncated,
+    };
+    serde_json::to_string_pretty(&json).expect("trace JSON is serializable")
+}
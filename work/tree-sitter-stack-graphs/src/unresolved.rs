@@ -0,0 +1,195 @@
+//! Reports of references that do not resolve
+//!
+//! Every reference node of the checked files is resolved, and the ones without a
+//! complete path to any definition are grouped by file. The report is printed as
+//! text ([`to_text`]):
+//!
+//! ```text
+//! main.py
+//!   2:1: `missing`
+//!   4:7: `helper`
+//! Found 2 unresolved references in 1 files, 14 references checked
+//! ```
+//!
+//! or as JSON ([`to_json`]) with the same information:
+//!
+//! ```json
+//! {
+//!   "references": 14,
+//!   "unresolved": 2,
+//!   "files": [
+//!     {
+//!       "file": "main.py",
+//!       "references": [
+//!         { "symbol": "missing", "line": 2, "column": 1, "end_line": 2, "end_column": 8 }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Serialize;
+use stack_graphs::arena::Handle;
+use stack_graphs::graph::{File, Node, StackGraph};
+use stack_graphs::CancellationError;
+
+use crate::query::{self, Location};
+
+/// A reference without definitions
+pub struct UnresolvedReference {
+    pub symbol: String,
+    pub location: Location,
+}
+
+/// Unresolved references of a set of files
+#[derive(Default)]
+pub struct UnresolvedReport {
+    /// Number of references that were resolved
+    pub references: usize,
+    /// Unresolved references by file name, sorted by position within each file
+    pub files: BTreeMap<String, Vec<UnresolvedReference>>,
+}
+
+impl UnresolvedReport {
+    /// Returns the total number of unresolved references
+    pub fn unresolved(&self) -> usize {
+        self.files.values().map(Vec::len).sum()
+    }
+
+    /// Formats the report as text, grouped by file
+    pub fn to_text(&self) -> String {
+        to_text(self)
+    }
+
+    /// Formats the report as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        to_json(self)
+    }
+
+    /// Adds unresolved reference nodes of a graph to the report
+    ///
+    /// # Arguments
+    /// * `graph` - The graph the references belong to
+    /// * `checked` - Number of references that were resolved
+    /// * `unresolved` - The references without definitions
+    pub fn add(&mut self, graph: &StackGraph, checked: usize, unresolved: &[Handle<Node>]) {
+        self.references += checked;
+        for &reference in unresolved {
+            let Some(location) = Location::of_node(graph, reference) else {
+                continue;
+            };
+            let symbol = query::node_symbol(graph, reference)
+                .unwrap_or_default()
+                .to_string();
+            self.files
+                .entry(location.file.clone())
+                .or_default()
+                .push(UnresolvedReference { symbol, location });
+        }
+        for references in self.files.values_mut() {
+            references.sort_by(|a, b| a.location.cmp(&b.location));
+        }
+    }
+}
+
+/// Resolves every reference in the given files of a graph
+///
+/// # Arguments
+/// * `graph` - The graph containing the files and everything they refer to
+/// * `files` - The files whose references are checked
+///
+/// # Returns
+/// The report of the references that have no definitions
+pub fn check_files(
+    graph: &StackGraph,
+    files: &[Handle<File>],
+) -> Result<UnresolvedReport, CancellationError> {
+    let files = files.iter().copied().collect::<HashSet<_>>();
+    let references = graph
+        .iter_nodes()
+        .filter(|&node| graph[node].is_reference())
+        .filter(|&node| {
+            graph[node]
+                .id()
+                .file()
+                .is_some_and(|file| files.contains(&file))
+        })
+        .collect::<Vec<_>>();
+
+    let checked = references.len();
+    let unresolved = query::find_unresolved(graph, references)?;
+    let mut report = UnresolvedReport::default();
+    report.add(graph, checked, &unresolved);
+    Ok(report)
+}
+
+/// Formats the report as described in the module documentation
+pub fn to_text(report: &UnresolvedReport) -> String {
+    let mut text = String::new();
+    for (file, references) in &report.files {
+        text.push_str(&format!("{}\n", file));
+        for reference in references {
+            text.push_str(&format!(
+                "  {}:{}: `{}`\n",
+                reference.location.start_line, reference.location.start_column, reference.symbol
+            ));
+        }
+    }
+    text.push_str(&format!(
+        "Found {} unresolved references in {} files, {} references checked\n",
+        report.unresolved(),
+        report.files.len(),
+        report.references
+    ));
+    text
+}
+
+#[derive(Serialize)]
+struct ReportJson<'a> {
+    references: usize,
+    unresolved: usize,
+    files: Vec<FileJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct FileJson<'a> {
+    file: &'a str,
+    references: Vec<ReferenceJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct ReferenceJson<'a> {
+    symbol: &'a str,
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+/// Formats the report as JSON as described in the module documentation
+pub fn to_json(report: &UnresolvedReport) -> String {
+    let json = ReportJson {
+        references: report.references,
+        unresolved: report.unresolved(),
+        files: report
+            .files
+            .iter()
+            .map(|(file, references)| FileJson {
+                file,
+                references: references
+                    .iter()
+                    .map(|reference| ReferenceJson {
+                        symbol: &reference.symbol,
+                        line: reference.location.start_line,
+                        column: reference.location.start_column,
+                        end_line: reference.location.end_line,
+                        end_column: reference.location.end_column,
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&json).expect("report is serializable")
+}
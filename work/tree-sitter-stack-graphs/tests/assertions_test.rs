@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+use tempfile::TempDir;
+
+mod common;
+
+use common::{fixtures, run, stderr};
+
+/// Checks the assertions of a fixture with the `test` command, run from `dir`
+fn run_test(dir: &Path, fixture: &str) -> Output {
+    run(dir, &["test", fixture])
+}
+
+/// Checks one fixture, a file or a directory built as one project so that its
+/// files can refer to each other, and reports its mismatches on failure
+fn check_fixture(dir: &str, case: &str) {
+    let output = run_test(&fixtures().join(dir), case);
+    assert!(
+        output.status.success(),
+        "{}/{}:\n{}",
+        dir,
+        case,
+        stderr(&output)
+    );
+}
+
+// One test per file or directory in the fixture directories, generated by build.rs
+include!(concat!(env!("OUT_DIR"), "/fixture_tests.rs"));
+
+#[test]
+fn test_mismatch_is_reported_with_line() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("main.py"),
+        "x = 1\nprint(x)\n#     ^ defined: 5\nmissing\n",
+    )
+    .unwrap();
+
+    let output = run_test(dir.path(), "main.py");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("main.py:3: `x` at 2:7 resolves to [1], expected [5]"),
+        "{}",
+        stderr
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("main.py: FAILED (1 assertions)"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_malformed_assertion() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("main.py"), "x = 1\nx\n# ^ defines: x\n").unwrap();
+
+    let output = run_test(dir.path(), "main.py");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("main.py:3: expected `defined:`, got `defines: x`"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,167 @@
+use std::fs;
+
+use tempfile::TempDir;
+
+mod common;
+
+use common::{run, stdout};
+
+/// Creates a project whose `main.py` uses builtins and a standard library module
+fn project() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("main.py"), "import sys\nprint(sys.path)\n").unwrap();
+    dir
+}
+
+#[test]
+fn test_builtins_resolve_to_the_stub() {
+    let dir = project();
+
+    let output = run(dir.path(), &["--builtins", "definition", "main.py:2:1"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    let definition = stdout(&output);
+    assert!(
+        definition.contains("<builtin>/python.builtins:"),
+        "{}",
+        definition
+    );
+    assert!(
+        definition.contains("definition of `print`"),
+        "{}",
+        definition
+    );
+
+    let output = run(dir.path(), &["--builtins", "definition", "main.py:2:11"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    let definition = stdout(&output);
+    assert!(
+        definition.contains("<builtin>/python.builtins:"),
+        "{}",
+        definition
+    );
+    assert!(
+        definition.contains("definition of `path`"),
+        "{}",
+        definition
+    );
+}
+
+#[test]
+fn test_local_definition_shadows_builtin() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("main.py"),
+        "def print(*args):\n    pass\n\nprint(len([]))\n",
+    )
+    .unwrap();
+
+    let output = run(dir.path(), &["--builtins", "definition", "main.py:4:1"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(stdout(&output), "main.py:1:5-1:10: definition of `print`\n");
+
+    // Names the module does not define still resolve to the stub
+    let output = run(dir.path(), &["--builtins", "definition", "main.py:4:7"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(
+        stdout(&output).contains("<builtin>/python.builtins:"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn test_builtins_are_disabled_by_default() {
+    let dir = project();
+
+    let output = run(dir.path(), &["definition", "main.py:2:1"]);
+    assert!(!output.status.success());
+    assert!(
+        stdout(&output).contains("`print` has no definitions"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn test_custom_stub() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("stub.txt"),
+        "# names\nhelper\n\n[tools.fs]\nread\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("main.py"),
+        "import tools.fs\nhelper()\ntools.fs.read()\nprint()\n",
+    )
+    .unwrap();
+
+    let output = run(
+        dir.path(),
+        &["--builtins-stub", "stub.txt", "definition", "main.py:2:1"],
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(
+        stdout(&output).contains("stub.txt:2:1-2:7: definition of `helper`"),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = run(
+        dir.path(),
+        &["--builtins-stub", "stub.txt", "definition", "main.py:3:10"],
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(
+        stdout(&output).contains("stub.txt:5:1-5:5: definition of `read`"),
+        "{}",
+        stdout(&output)
+    );
+
+    // The custom stub replaces the built-in one
+    let output = run(
+        dir.path(),
+        &["--builtins-stub", "stub.txt", "definition", "main.py:4:1"],
+    );
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_malformed_stub_is_reported() {
+    let dir = project();
+    fs::write(dir.path().join("stub.txt"), "print\n[sys\n").unwrap();
+
+    let output = run(
+        dir.path(),
+        &["--builtins-stub", "stub.txt", "definition", "main.py:2:1"],
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("stub.txt:2: unclosed section `[sys`"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_builtins_are_indexed() {
+    let dir = project();
+
+    let output = run(dir.path(), &["--builtins", "index", "main.py"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    // The stub is stored but not reported as an indexed source file
+    assert!(
+        stdout(&output).contains("1 added, 0 updated, 0 removed, 0 unchanged, 0 failed"),
+        "{}",
+        stdout(&output)
+    );
+
+    let output = run(dir.path(), &["definition", "--use-index", "main.py:2:1"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(
+        stdout(&output).contains("<builtin>/python.builtins:"),
+        "{}",
+        stdout(&output)
+    );
+}
@@ -0,0 +1,44 @@
+//! Helpers shared by the integration tests that run the binary
+//!
+//! Every test file is its own crate and uses only some of these.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Runs the binary inside `dir`
+pub fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tree-sitter-stack-graphs"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Runs the binary inside `dir` against the database `index.sqlite`
+pub fn run_indexed(dir: &Path, args: &[&str]) -> Output {
+    let mut indexed = vec!["--database", "index.sqlite"];
+    indexed.extend(args);
+    run(dir, &indexed)
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// Directory of the fixture directories under `tests/fixtures`
+pub fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// Runs the binary from the Python fixture directory and returns its stdout,
+/// failing the test if it does not succeed
+pub fn run_in_fixtures(args: &[&str]) -> String {
+    let output = run(&fixtures().join("python"), args);
+    assert!(output.status.success(), "{}", stderr(&output));
+    String::from_utf8(output.stdout).unwrap()
+}
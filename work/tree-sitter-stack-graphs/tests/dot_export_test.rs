@@ -0,0 +1,77 @@
+mod common;
+
+use common::run_in_fixtures;
+
+#[test]
+fn test_dot_labels() {
+    let dot = run_in_fixtures(&["analyze", "--dot", "functions.py"]);
+
+    // Definitions show kind, symbol, flags and span
+    assert!(
+        dot.contains("pop_symbol [")
+            && dot.contains("`greet`\\ndefinition\\nfunctions.py:1:5-1:10"),
+        "{}",
+        dot
+    );
+    // References are marked as such
+    assert!(
+        dot.contains("`message`\\nreference\\nfunctions.py:5:12-5:19"),
+        "{}",
+        dot
+    );
+    // The root node has no file
+    assert!(dot.contains("label=\"root [1]\""), "{}", dot);
+    // Edges carry their precedence
+    assert!(dot.contains(" [label=\"0\"];"), "{}", dot);
+    assert!(!dot.contains("Handle"), "{}", dot);
+}
+
+#[test]
+fn test_dot_clusters_and_file_filter() {
+    let dot = run_in_fixtures(&["analyze", "--dot", "functions.py", "classes.py"]);
+    assert!(dot.contains("subgraph \"cluster_functions.py\""), "{}", dot);
+    assert!(dot.contains("subgraph \"cluster_classes.py\""), "{}", dot);
+
+    let dot = run_in_fixtures(&[
+        "analyze",
+        "--dot",
+        "--dot-file",
+        "classes.py",
+        "functions.py",
+        "classes.py",
+    ]);
+    assert!(dot.contains("subgraph \"cluster_classes.py\""), "{}", dot);
+    assert!(!dot.contains("cluster_functions.py"), "{}", dot);
+    assert!(!dot.contains("`greet`"), "{}", dot);
+    // The root node is shared by all files and always kept
+    assert!(dot.contains("label=\"root [1]\""), "{}", dot);
+}
+
+#[test]
+fn test_dot_hide_scopes() {
+    let dot = run_in_fixtures(&["analyze", "--dot", "--dot-hide-scopes", "functions.py"]);
+    assert!(!dot.contains("label=\"scope ["), "{}", dot);
+    assert!(dot.contains("[style=dashed]"), "{}", dot);
+    assert!(dot.contains("`greet`\\ndefinition"), "{}", dot);
+}
+
+#[test]
+fn test_dot_focus_neighbourhood() {
+    let dot = run_in_fixtures(&[
+        "analyze",
+        "--dot",
+        "--dot-focus",
+        "functions.py:5:12",
+        "--dot-depth",
+        "1",
+        "functions.py",
+    ]);
+    assert!(
+        dot.contains("`message`\\nreference\\nfunctions.py:5:12-5:19"),
+        "{}",
+        dot
+    );
+    // Nodes far from the reference are left out
+    assert!(!dot.contains("`main`"), "{}", dot);
+    assert!(!dot.contains("label=\"root [1]\""), "{}", dot);
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::Path;
+
+use stack_graph_tools::{Error, Indexer};
+use tempfile::TempDir;
+
+mod common;
+
+use common::{run, stderr};
+
+#[test]
+fn test_syntax_error_shows_source_line() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("main.py"), "x = 1\ndef f(:\n    pass\n").unwrap();
+
+    let output = run(dir.path(), &["analyze", "main.py"]);
+    assert!(!output.status.success());
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("main.py: error: missing `)` at 2:7"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains(" --> main.py:2:7\n"), "{}", stderr);
+    assert!(stderr.contains("2 | def f(:\n"), "{}", stderr);
+    assert!(stderr.contains("  |       ^\n"), "{}", stderr);
+}
+
+#[test]
+fn test_execution_error_names_rules_and_file() {
+    let dir = TempDir::new().unwrap();
+    // `@m.b` is never defined, which only shows when the stanza runs
+    fs::write(
+        dir.path().join("rules.tsg"),
+        "(module) @m {\n  node @m.a\n  edge @m.a -> @m.b\n}\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("main.py"), "x = 1\n").unwrap();
+
+    let output = run(dir.path(), &["--rules", "rules.tsg", "analyze", "main.py"]);
+    assert!(!output.status.success());
+    let stderr = stderr(&output);
+    assert!(stderr.contains("main.py: error: "), "{}", stderr);
+    assert!(stderr.contains("rules.tsg"), "{}", stderr);
+}
+
+#[test]
+fn test_compile_error_is_not_a_file_failure() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("broken.tsg"), "(module) @m {\n").unwrap();
+    fs::write(dir.path().join("main.py"), "x = 1\n").unwrap();
+
+    let output = run(dir.path(), &["--rules", "broken.tsg", "analyze", "main.py"]);
+    assert!(!output.status.success());
+    let stderr = stderr(&output);
+    assert!(stderr.starts_with("error: "), "{}", stderr);
+    assert!(stderr.contains("broken.tsg"), "{}", stderr);
+}
+
+#[test]
+fn test_parse_error_is_structured() {
+    let indexer = Indexer::new().unwrap();
+    let result = indexer.build_source(Path::new("main.py"), "x = 1\ndef f(:\n    pass\n");
+
+    let Err(error) = result else {
+        panic!("expected a parse error");
+    };
+    let Error::Parse { path, errors } = &error else {
+        panic!("expected a parse error, got {}", error);
+    };
+    assert_eq!(path, Path::new("main.py"));
+    assert!(!errors.is_empty());
+    assert_eq!(errors[0].excerpt.line, 2);
+    assert_eq!(errors[0].excerpt.text, "def f(:");
+    assert!(error.to_string().starts_with("main.py: "), "{}", error);
+    assert!(
+        error.display_pretty().to_string().contains("2 | def f(:"),
+        "{}",
+        error.display_pretty()
+    );
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+use std::process::Command;
+
+use insta::assert_snapshot;
+
+/// Converts the small hand-written graph in `tests/fixtures/export` to a format
+fn convert(format: &str) -> String {
+    convert_fixture("small.json", format)
+}
+
+/// Converts a hand-written graph in `tests/fixtures/export` to a format
+fn convert_fixture(name: &str, format: &str) -> String {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/export")
+        .join(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_tree-sitter-stack-graphs"))
+        .args(["convert", "--to", format])
+        .arg(fixture)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_dot_backend() {
+    assert_snapshot!(convert("dot"));
+}
+
+#[test]
+fn test_mermaid_backend() {
+    assert_snapshot!(convert("mermaid"));
+}
+
+/// Symbols and file names such as `<builtins>` must not be read as HTML
+#[test]
+fn test_mermaid_escapes_html() {
+    assert_snapshot!(convert_fixture("builtins.json", "mermaid"));
+}
+
+#[test]
+fn test_graphml_backend() {
+    assert_snapshot!(convert("graphml"));
+}
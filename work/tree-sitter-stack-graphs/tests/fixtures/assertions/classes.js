@@ -0,0 +1,26 @@
+class Counter {
+    start = 0;
+
+    increment(step) {
+        return this.start + step;
+        //          ^ defined: 2
+        //                  ^ defined: 4
+    }
+
+    twice() {
+        return this.increment(this.increment(1));
+        //          ^ defined: 4
+        //                         ^ defined: 4
+    }
+
+    reset() {
+        return start;
+        //     ^ defined:
+    }
+}
+
+const counter = new Counter();
+//                  ^ defined: 1
+const next = Counter.twice(counter);
+//                   ^ defined: 10
+//                         ^ defined: 22
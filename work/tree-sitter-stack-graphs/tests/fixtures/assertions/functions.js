@@ -0,0 +1,30 @@
+function greet(name) {
+    const message = "Hello, " + name;
+    //                          ^ defined: 1
+    return message;
+    //     ^ defined: 2
+}
+
+const shout = (text) => text.toUpperCase();
+//                      ^ defined: 8
+const twice = x => greet(x) + greet(x);
+//                 ^ defined: 1
+//                       ^ defined: 10
+const result = shout(twice("world"));
+//             ^ defined: 8
+//                   ^ defined: 10
+var hoisted = later();
+//            ^ defined: 19
+
+function later() {
+    return hoisted;
+    //     ^ defined: 16
+}
+
+const factorial = function fact(n) {
+    return n <= 1 ? 1 : n * fact(n - 1);
+    //     ^ defined: 24
+    //                      ^ defined: 24
+};
+const leaked = fact;
+//             ^ defined:
@@ -0,0 +1,15 @@
+import fs from "fs";
+import * as path from "path";
+import { readFile, writeFile as write } from "fs/promises";
+
+const joined = path.join("a", "b");
+//             ^ defined: 2
+const exists = fs.existsSync(joined);
+//             ^ defined: 1
+//                           ^ defined: 5
+const loaded = readFile(joined).then((data) => write(joined, data));
+//             ^ defined: 3
+//                                             ^ defined: 3
+//                                                           ^ defined: 10
+const missing = writeFile;
+//              ^ defined:
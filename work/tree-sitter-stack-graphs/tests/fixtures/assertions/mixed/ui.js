@@ -0,0 +1,10 @@
+function render(value) {
+    return value;
+    //     ^ defined: 1
+}
+
+const result = render(1);
+//             ^ defined: 1
+// Python definitions are not visible from JavaScript
+const other = helper(1);
+//            ^ defined:
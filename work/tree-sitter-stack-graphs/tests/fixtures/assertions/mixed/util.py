@@ -0,0 +1,7 @@
+def helper(value):
+    return value
+    #      ^ defined: 1
+
+
+result = helper(1)
+#        ^ defined: 1
@@ -0,0 +1,21 @@
+import pkg
+import pkg.util
+#          ^ defined: pkg/util.py:1
+from pkg import VERSION
+#    ^ defined: pkg/__init__.py:1
+#               ^ defined: pkg/__init__.py:4
+from pkg.util import DEFAULT as default_value
+#                    ^ defined: pkg/util.py:9
+import pkg.util as util_module
+
+result = pkg.util.helper(default_value)
+#        ^ defined: 1, 2, pkg/__init__.py:1
+#                 ^ defined: pkg/util.py:4
+#                        ^ defined: 7, pkg/util.py:9
+value = util_module.DEFAULT
+#       ^ defined: 9, pkg/util.py:1
+#                   ^ defined: pkg/util.py:9
+version = pkg.VERSION
+#             ^ defined: pkg/__init__.py:4
+missing = helper
+#         ^ defined:
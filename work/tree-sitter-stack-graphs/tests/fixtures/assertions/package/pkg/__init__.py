@@ -0,0 +1,4 @@
+from pkg.util import helper
+#                    ^ defined: pkg/util.py:4
+
+VERSION = "1.0"
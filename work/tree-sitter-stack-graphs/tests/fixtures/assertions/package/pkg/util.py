@@ -0,0 +1,9 @@
+"""Helpers shared by the package."""
+
+
+def helper(value):
+    return value
+    #      ^ defined: 4
+
+
+DEFAULT = 3
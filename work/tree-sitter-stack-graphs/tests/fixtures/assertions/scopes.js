@@ -0,0 +1,29 @@
+let value = 1;
+if (value > 0) {
+//  ^ defined: 1
+    let local = value + 1;
+    //          ^ defined: 1
+    var shared = local;
+    //           ^ defined: 4
+}
+const after = shared;
+//            ^ defined: 6
+const missing = local;
+//              ^ defined:
+
+const items = [1, 2, 3];
+for (const item of items) {
+//                 ^ defined: 14
+    console.log(item);
+    //          ^ defined: 15
+}
+const last = item;
+//           ^ defined:
+
+try {
+    JSON.parse(after);
+    //         ^ defined: 9
+} catch (error) {
+    const reported = { error };
+    //                 ^ defined: 26
+}
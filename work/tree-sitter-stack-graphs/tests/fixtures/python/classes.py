@@ -0,0 +1,24 @@
+class Counter:
+    start = 0
+
+    def increment(self, step):
+        return step + 1
+        #      ^ defined: 4
+
+
+counter = Counter()
+#         ^ defined: 1
+print(Counter.start)
+#     ^ defined: 1
+#             ^ defined: 2
+Counter.increment(counter, 1)
+#       ^ defined: 4
+#                 ^ defined: 9
+
+
+class Config:
+    debug = True
+
+    def is_debug(self):
+        return debug
+        #      ^ defined:
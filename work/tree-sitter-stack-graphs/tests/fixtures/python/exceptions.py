@@ -0,0 +1,9 @@
+def parse(text):
+    try:
+        return int(text)
+    except ValueError as error:
+        print(error)
+        #     ^ defined: 4
+    except (TypeError, KeyError) as other:
+        return other
+        #      ^ defined: 7
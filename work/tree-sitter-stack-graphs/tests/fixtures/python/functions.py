@@ -0,0 +1,11 @@
+def greet(name, greeting="hello"):
+    message = greeting + name
+    #         ^ defined: 1
+    #                    ^ defined: 1
+    return message
+    #      ^ defined: 2
+
+
+def main():
+    return greet("world")
+    #      ^ defined: 1
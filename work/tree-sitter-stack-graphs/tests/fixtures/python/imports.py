@@ -0,0 +1,13 @@
+import os.path
+import collections as col
+from json import dumps
+from typing import List as L
+
+joined = os.path.join("a", "b")
+#        ^ defined: 1
+ordered = col.OrderedDict()
+#         ^ defined: 2
+text = dumps({})
+#      ^ defined: 3
+alias = L
+#       ^ defined: 4
@@ -0,0 +1,28 @@
+total = 0
+
+
+def outer():
+    count = 0
+
+    def inner():
+        nonlocal count
+        count = count + 1
+        #       ^ defined: 5, 9
+        return count
+        #      ^ defined: 5, 9
+
+    return inner
+    #      ^ defined: 7
+
+
+def reset():
+    global total
+    total = 0
+    return total
+    #      ^ defined: 1, 20
+
+
+squares = [n * n for n in range(10)]
+#              ^ defined: 25
+leaked = n
+#        ^ defined:
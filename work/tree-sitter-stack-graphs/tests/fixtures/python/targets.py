@@ -0,0 +1,13 @@
+for i, (j, k) in [(1, (2, 3))]:
+    total = i + j + k
+    #       ^ defined: 1
+    #           ^ defined: 1
+    #               ^ defined: 1
+
+[first, [second, third]] = [1, [2, 3]]
+print(second, third)
+#     ^ defined: 7
+#             ^ defined: 7
+
+pairs = [a + b + c for a, (b, c) in [(1, (2, 3))]]
+#                ^ defined: 12
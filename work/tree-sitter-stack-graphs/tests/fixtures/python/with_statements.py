@@ -0,0 +1,7 @@
+def read(path, other):
+    with open(path) as fh, open(other) as (head, tail):
+        data = fh.read()
+        #      ^ defined: 2
+        return data, head, tail
+        #            ^ defined: 2
+        #                  ^ defined: 2
@@ -0,0 +1,166 @@
+use std::fs;
+
+use tempfile::TempDir;
+
+mod common;
+
+use common::{run_indexed, stdout};
+
+fn create_project() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("pkg")).unwrap();
+    fs::write(
+        dir.path().join("pkg/util.py"),
+        "def helper(x):\n    return x\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("pkg/main.py"),
+        "def run():\n    pass\n\nrun()\n",
+    )
+    .unwrap();
+    dir
+}
+
+#[test]
+fn test_index_status_clean() {
+    let dir = create_project();
+
+    let output = run_indexed(dir.path(), &["index", "pkg"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(dir.path().join("index.sqlite").exists());
+
+    let output = run_indexed(dir.path(), &["status"]);
+    assert!(output.status.success());
+    let status = stdout(&output);
+    assert!(status.contains("pkg/main.py: indexed"), "{}", status);
+    assert!(status.contains("pkg/util.py: indexed"), "{}", status);
+
+    let output = run_indexed(dir.path(), &["clean", "pkg/util.py"]);
+    assert!(output.status.success());
+    let status = stdout(&run_indexed(dir.path(), &["status"]));
+    assert!(status.contains("pkg/main.py: indexed"), "{}", status);
+    assert!(!status.contains("pkg/util.py"), "{}", status);
+
+    let output = run_indexed(dir.path(), &["clean"]);
+    assert!(output.status.success());
+    let status = stdout(&run_indexed(dir.path(), &["status"]));
+    assert!(status.contains("0 files"), "{}", status);
+}
+
+#[test]
+fn test_definition_from_index() {
+    let dir = create_project();
+    assert!(run_indexed(dir.path(), &["index", "pkg"]).status.success());
+
+    let output = run_indexed(
+        dir.path(),
+        &["definition", "--use-index", "pkg/main.py:4:1"],
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(
+        stdout(&output).contains("pkg/main.py:1:5-1:8: definition of `run`"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn test_incremental_index() {
+    let dir = create_project();
+    let output = run_indexed(dir.path(), &["index", "pkg"]);
+    assert!(
+        stdout(&output).contains("2 added, 0 updated, 0 removed, 0 unchanged, 0 failed"),
+        "{}",
+        stdout(&output)
+    );
+
+    // Nothing changed, so nothing is rebuilt
+    let output = run_indexed(dir.path(), &["index", "pkg"]);
+    assert!(
+        stdout(&output).contains("0 added, 0 updated, 0 removed, 2 unchanged, 0 failed"),
+        "{}",
+        stdout(&output)
+    );
+
+    fs::write(dir.path().join("pkg/main.py"), "def run():\n    return 1\n").unwrap();
+    fs::remove_file(dir.path().join("pkg/util.py")).unwrap();
+    fs::write(dir.path().join("pkg/extra.py"), "value = 1\n").unwrap();
+
+    let output = run_indexed(dir.path(), &["index", "pkg"]);
+    let summary = stdout(&output);
+    assert!(summary.contains("pkg/extra.py: added"), "{}", summary);
+    assert!(summary.contains("pkg/main.py: updated"), "{}", summary);
+    assert!(summary.contains("pkg/util.py: removed"), "{}", summary);
+    assert!(
+        summary.contains("1 added, 1 updated, 1 removed, 0 unchanged, 0 failed"),
+        "{}",
+        summary
+    );
+
+    // --force rebuilds unchanged files
+    let output = run_indexed(dir.path(), &["index", "--force", "pkg"]);
+    assert!(
+        stdout(&output).contains("0 added, 2 updated, 0 removed, 0 unchanged, 0 failed"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn test_index_rebuilds_when_build_inputs_change() {
+    let dir = create_project();
+    fs::write(dir.path().join("empty.tsg"), "").unwrap();
+    let summary = |args: &[&str]| stdout(&run_indexed(dir.path(), args));
+
+    let output = summary(&["index", "pkg"]);
+    assert!(output.contains("2 added, 0 updated"), "{}", output);
+
+    // The tolerant flag is part of the tag, in both directions
+    let output = summary(&["index", "--tolerant", "pkg"]);
+    assert!(output.contains("0 added, 2 updated"), "{}", output);
+    let output = summary(&["index", "--tolerant", "pkg"]);
+    assert!(output.contains("2 unchanged"), "{}", output);
+    let output = summary(&["index", "pkg"]);
+    assert!(output.contains("0 added, 2 updated"), "{}", output);
+
+    // So is the path relative to the root, which gives the module path
+    let output = summary(&["index", "."]);
+    assert!(output.contains("0 added, 2 updated"), "{}", output);
+
+    // And the rules of the file's language
+    let output = summary(&["--rules", "python=empty.tsg", "index", "."]);
+    assert!(output.contains("0 added, 2 updated"), "{}", output);
+    // but not those of other languages
+    let output = summary(&[
+        "--rules",
+        "python=empty.tsg",
+        "--rules",
+        "javascript=empty.tsg",
+        "index",
+        ".",
+    ]);
+    assert!(output.contains("2 unchanged"), "{}", output);
+}
+
+#[test]
+fn test_index_reports_timeouts() {
+    let dir = create_project();
+
+    // A zero timeout cancels every file as soon as the builder checks the flag
+    let output = run_indexed(
+        dir.path(),
+        &["index", "--keep-going", "--timeout", "0", "pkg"],
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("pkg/main.py: timed out"), "{}", stderr);
+    assert!(stderr.contains("pkg/util.py: timed out"), "{}", stderr);
+
+    let status = stdout(&run_indexed(dir.path(), &["status"]));
+    assert!(
+        status.contains("pkg/main.py: error: timed out"),
+        "{}",
+        status
+    );
+}
@@ -0,0 +1,119 @@
+use std::fs;
+use std::process::Command;
+
+use tempfile::TempDir;
+
+mod common;
+
+use common::run_in_fixtures;
+
+/// Exports the fixtures to JSON and returns the path of the JSON file
+fn export(dir: &TempDir, files: &[&str]) -> String {
+    let json = dir.path().join("graph.json");
+    let json = json.to_str().unwrap().to_string();
+    let mut args = vec!["analyze", "--json", json.as_str()];
+    args.extend(files);
+    run_in_fixtures(&args);
+    json
+}
+
+#[test]
+fn test_json_schema() {
+    let dir = TempDir::new().unwrap();
+    let path = export(&dir, &["functions.py"]);
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+
+    assert_eq!(json["version"], 1);
+    assert_eq!(json["files"], serde_json::json!(["functions.py"]));
+    assert!(json["symbols"]
+        .as_array()
+        .unwrap()
+        .contains(&serde_json::json!("greet")));
+
+    let nodes = json["nodes"].as_array().unwrap();
+    assert_eq!(nodes[0]["id"], serde_json::json!({ "local_id": 1 }));
+    assert_eq!(nodes[0]["kind"], "root");
+    let greet = nodes
+        .iter()
+        .find(|node| node["symbol"] == "greet" && node["definition"] == true)
+        .unwrap();
+    assert_eq!(greet["kind"], "pop_symbol");
+    assert_eq!(greet["id"]["file"], "functions.py");
+    assert_eq!(
+        greet["span"],
+        serde_json::json!({
+            "start": { "line": 1, "column": 5 },
+            "end": { "line": 1, "column": 10 }
+        })
+    );
+
+    let edge = &json["edges"][0];
+    assert!(edge["source"]["local_id"].is_u64(), "{}", edge);
+    assert!(edge["sink"]["local_id"].is_u64(), "{}", edge);
+    assert!(edge["precedence"].is_i64(), "{}", edge);
+}
+
+#[test]
+fn test_json_round_trip() {
+    let dir = TempDir::new().unwrap();
+    let path = export(&dir, &["functions.py", "classes.py", "imports.py"]);
+    let exported = fs::read_to_string(&path).unwrap();
+
+    // Importing and exporting again gives the same JSON
+    let converted = run_in_fixtures(&["convert", &path]);
+    assert_eq!(converted.trim_end(), exported.trim_end());
+
+    let dot = run_in_fixtures(&["convert", "--to", "dot", &path]);
+    assert!(dot.contains("subgraph \"cluster_classes.py\""), "{}", dot);
+    assert!(
+        dot.contains("`greet`\\ndefinition\\nfunctions.py:1:5-1:10"),
+        "{}",
+        dot
+    );
+}
+
+#[test]
+fn test_convert_rejects_invalid_json() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("graph.json");
+    fs::write(
+        &path,
+        r#"{"version": 99, "files": [], "symbols": [], "nodes": [], "edges": []}"#,
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tree-sitter-stack-graphs"))
+        .args(["convert", path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("unsupported schema version 99"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_html_viewer() {
+    let dir = TempDir::new().unwrap();
+    let html = dir.path().join("graph.html");
+    run_in_fixtures(&["analyze", "--html", html.to_str().unwrap(), "functions.py"]);
+    let html = fs::read_to_string(html).unwrap();
+
+    // The page is self-contained: no external scripts or stylesheets
+    assert!(!html.contains("src=\"http"), "{}", html);
+    assert!(!html.contains("href=\"http"), "{}", html);
+    assert!(!html.contains("/*GRAPH_JSON*/"));
+
+    // The embedded graph is the JSON export
+    let start = html.find("id=\"graph-data\">").unwrap() + "id=\"graph-data\">".len();
+    let end = start + html[start..].find("</script>").unwrap();
+    let json: serde_json::Value = serde_json::from_str(&html[start..end]).unwrap();
+    assert_eq!(json["version"], 1);
+    assert_eq!(json["files"], serde_json::json!(["functions.py"]));
+
+    // Node kinds are coloured as in the other exports
+    assert!(html.contains("\"pop_symbol\":\"lightpink\""), "{}", html);
+}
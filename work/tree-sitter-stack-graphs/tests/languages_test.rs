@@ -0,0 +1,71 @@
+use std::fs;
+
+use tempfile::TempDir;
+
+mod common;
+
+use common::run;
+
+#[test]
+fn test_mixed_directory_uses_language_per_extension() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("src")).unwrap();
+    fs::write(dir.path().join("src/util.py"), "def helper():\n    pass\n").unwrap();
+    fs::write(dir.path().join("src/app.js"), "function main() {}\n").unwrap();
+    fs::write(dir.path().join("src/notes.txt"), "not source\n").unwrap();
+
+    let output = run(dir.path(), &["analyze", "src"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("src/app.js: "), "{}", stdout);
+    assert!(stdout.contains("src/util.py: "), "{}", stdout);
+    // Files of unknown languages are not picked up when walking directories
+    assert!(!stdout.contains("notes.txt"), "{}", stdout);
+    assert!(stdout.contains("Analyzed 2 of 2 files"), "{}", stdout);
+}
+
+#[test]
+fn test_unknown_extension_is_reported() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("notes.txt"), "not source\n").unwrap();
+
+    let output = run(dir.path(), &["analyze", "--keep-going", "notes.txt"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("notes.txt: error: no language is registered for this file extension"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_rules_override_applies_to_one_language() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("broken.tsg"), "(program) @prog {\n").unwrap();
+    fs::write(dir.path().join("app.js"), "function main() {}\n").unwrap();
+
+    let output = run(
+        dir.path(),
+        &["--rules", "javascript=broken.tsg", "analyze", "app.js"],
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("broken.tsg"), "{}", stderr);
+
+    let output = run(
+        dir.path(),
+        &["--rules", "ruby=broken.tsg", "analyze", "app.js"],
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("unknown language `ruby`, expected one of python, javascript"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use stack_graph_tools::{BuildOptions, Error, ExportFormat, Exporter, Indexer, Position, Query};
+use tempfile::TempDir;
+
+/// Creates a project in which `main.py` calls a function of `util.py`
+fn project() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("main.py"),
+        "from util import helper\n\nhelper()\nmissing()\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("util.py"), "def helper():\n    pass\n").unwrap();
+    dir
+}
+
+fn position(dir: &Path, file: &str, line: usize, column: usize) -> Position {
+    Position {
+        path: dir.join(file),
+        line,
+        column,
+    }
+}
+
+#[test]
+fn test_build_and_query() {
+    let dir = project();
+    let analysis = Indexer::new()
+        .unwrap()
+        .build(&[dir.path().to_path_buf()])
+        .unwrap();
+    assert_eq!(analysis.files.len(), 2);
+    assert_eq!(analysis.failures().count(), 0);
+
+    let query = Query::new(&analysis.graph);
+    let resolution = query
+        .definitions(&position(dir.path(), "main.py", 3, 1))
+        .unwrap()
+        .unwrap();
+    assert_eq!(resolution.symbol, "helper");
+    assert!(
+        resolution
+            .definitions
+            .iter()
+            .any(|location| location.file.ends_with("util.py") && location.start_line == 1),
+        "{:?}",
+        resolution.definitions
+    );
+
+    let references = query
+        .references(&position(dir.path(), "util.py", 1, 5))
+        .unwrap()
+        .unwrap();
+    assert_eq!(references.symbol, "helper");
+    assert!(!references.nodes.is_empty());
+
+    let report = query.unresolved(&analysis.built_files()).unwrap();
+    assert_eq!(report.unresolved(), 1);
+}
+
+#[test]
+fn test_query_unknown_file() {
+    let dir = project();
+    let analysis = Indexer::new()
+        .unwrap()
+        .build(&[dir.path().join("util.py")])
+        .unwrap();
+
+    let result = Query::new(&analysis.graph).definitions(&position(dir.path(), "main.py", 3, 1));
+    assert!(matches!(result, Err(Error::NotIndexed(_))));
+}
+
+#[test]
+fn test_build_reports_failures() {
+    let dir = project();
+    fs::write(dir.path().join("notes.txt"), "not source\n").unwrap();
+    let indexer = Indexer::new().unwrap().with_options(BuildOptions {
+        keep_going: true,
+        ..BuildOptions::default()
+    });
+
+    let analysis = indexer
+        .build(&[dir.path().join("notes.txt"), dir.path().join("util.py")])
+        .unwrap();
+    let failures = analysis.failures().collect::<Vec<_>>();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].path, dir.path().join("notes.txt"));
+    assert_eq!(analysis.built_files(), [dir.path().join("util.py")]);
+
+    let missing = indexer.build(&[PathBuf::from("does/not/exist")]);
+    assert!(matches!(missing, Err(Error::Io { .. })));
+}
+
+#[test]
+fn test_build_source_and_export() {
+    let graph = Indexer::new()
+        .unwrap()
+        .build_source(Path::new("example.py"), "x = 1\nprint(x)\n")
+        .unwrap();
+
+    let text = Exporter::new(&graph).render(ExportFormat::Text);
+    assert!(text.contains("example.py#"), "{}", text);
+    let json = Exporter::new(&graph).render(ExportFormat::Json);
+    assert!(json.ends_with("}\n"), "{}", json);
+    assert!(Exporter::new(&graph)
+        .render(ExportFormat::Dot)
+        .starts_with("digraph"));
+    assert_eq!("graphml".parse::<ExportFormat>(), Ok(ExportFormat::Graphml));
+    assert!("svg".parse::<ExportFormat>().is_err());
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+/// Frames a JSON-RPC message with its `Content-Length` header
+fn frame(message: Value) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+fn request(id: u64, method: &str, params: Value) -> String {
+    frame(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+}
+
+fn notification(method: &str, params: Value) -> String {
+    frame(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+}
+
+fn position(uri: &str, line: u32, character: u32) -> Value {
+    json!({
+        "textDocument": { "uri": uri },
+        "position": { "line": line, "character": character }
+    })
+}
+
+/// The `lsp` subcommand of the main binary
+fn subcommand() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_tree-sitter-stack-graphs"));
+    command.arg("lsp");
+    command
+}
+
+/// The standalone server binary
+fn standalone() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_tree-sitter-stack-graphs-lsp"))
+}
+
+/// Sends the scripted messages to the server and returns its responses by ID
+fn run_script(mut server: Command, messages: &[String]) -> HashMap<u64, Value> {
+    let mut child = server
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for message in messages {
+        stdin.write_all(message.as_bytes()).unwrap();
+    }
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut responses = HashMap::new();
+    let mut rest = String::from_utf8(output.stdout).unwrap();
+    while let Some(header_end) = rest.find("\r\n\r\n") {
+        let length = rest[..header_end]
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .unwrap()
+            .parse::<usize>()
+            .unwrap();
+        let body_start = header_end + 4;
+        let message: Value = serde_json::from_str(&rest[body_start..body_start + length]).unwrap();
+        if let Some(id) = message["id"].as_u64() {
+            responses.insert(id, message);
+        }
+        rest = rest[body_start + length..].to_string();
+    }
+    responses
+}
+
+#[test]
+fn test_definition_references_hover_and_change() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    fs::write(root.join("util.py"), "def helper():\n    return 1\n").unwrap();
+    fs::write(
+        root.join("main.py"),
+        "from util import helper\n\nhelper()\n",
+    )
+    .unwrap();
+    let root_uri = format!("file://{}", root.display());
+    let main_uri = format!("{}/main.py", root_uri);
+    let util_uri = format!("{}/util.py", root_uri);
+
+    let responses = run_script(
+        standalone(),
+        &[
+            request(
+                1,
+                "initialize",
+                json!({
+                    "processId": null,
+                    "rootUri": root_uri,
+                    "capabilities": {},
+                    "workspaceFolders": [{ "uri": root_uri, "name": "project" }]
+                }),
+            ),
+            notification("initialized", json!({})),
+            request(2, "textDocument/definition", position(&main_uri, 2, 0)),
+            request(
+                3,
+                "textDocument/references",
+                json!({
+                    "textDocument": { "uri": util_uri },
+                    "position": { "line": 0, "character": 4 },
+                    "context": { "includeDeclaration": false }
+                }),
+            ),
+            request(4, "textDocument/hover", position(&main_uri, 2, 0)),
+            notification(
+                "textDocument/didOpen",
+                json!({
+                    "textDocument": {
+                        "uri": main_uri,
+                        "languageId": "python",
+                        "version": 1,
+                        "text": "from util import helper\n\nhelper()\n"
+                    }
+                }),
+            ),
+            notification(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": { "uri": main_uri, "version": 2 },
+                    "contentChanges": [{ "text": "from util import helper\n\n\nhelper()\nmissing()\n" }]
+                }),
+            ),
+            request(5, "textDocument/definition", position(&main_uri, 3, 0)),
+            request(6, "textDocument/definition", position(&main_uri, 4, 0)),
+            request(7, "shutdown", Value::Null),
+            notification("exit", Value::Null),
+        ],
+    );
+
+    let capabilities = &responses[&1]["result"]["capabilities"];
+    assert_eq!(capabilities["definitionProvider"], true);
+    assert_eq!(capabilities["hoverProvider"], true);
+
+    // `helper()` resolves to the import and to the function in util.py
+    let definitions = responses[&2]["result"].as_array().unwrap();
+    assert!(
+        definitions.contains(&json!({
+            "uri": util_uri,
+            "range": {
+                "start": { "line": 0, "character": 4 },
+                "end": { "line": 0, "character": 10 }
+            }
+        })),
+        "{:#}",
+        responses[&2]
+    );
+
+    let references = responses[&3]["result"].as_array().unwrap();
+    assert!(
+        references
+            .iter()
+            .any(|location| location["uri"] == main_uri.as_str()
+                && location["range"]["start"] == json!({ "line": 2, "character": 0 })),
+        "{:#}",
+        responses[&3]
+    );
+
+    let hover = responses[&4]["result"]["contents"]["value"]
+        .as_str()
+        .unwrap();
+    assert!(hover.contains("`helper`"), "{}", hover);
+    assert!(hover.contains("function at `util.py:1:5`"), "{}", hover);
+
+    // After the change the call moved down a line and a new unresolved name follows it
+    let definitions = responses[&5]["result"].as_array().unwrap();
+    assert!(
+        definitions
+            .iter()
+            .any(|location| location["uri"] == util_uri.as_str()),
+        "{:#}",
+        responses[&5]
+    );
+    assert_eq!(responses[&6]["result"], json!([]));
+    assert!(responses[&7]["error"].is_null(), "{:#}", responses[&7]);
+}
+
+#[test]
+fn test_unknown_request_is_rejected() {
+    let dir = TempDir::new().unwrap();
+    let root_uri = format!("file://{}", dir.path().canonicalize().unwrap().display());
+
+    let responses = run_script(
+        subcommand(),
+        &[
+            request(
+                1,
+                "initialize",
+                json!({ "processId": null, "rootUri": root_uri, "capabilities": {} }),
+            ),
+            notification("initialized", json!({})),
+            request(2, "textDocument/completion", position(&root_uri, 0, 0)),
+            request(3, "shutdown", Value::Null),
+            notification("exit", Value::Null),
+        ],
+    );
+
+    assert_eq!(responses[&2]["error"]["code"], -32601);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_lsp_subcommand_exits_on_ctrl_c() {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    let mut child = subcommand()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    // Give the server time to start before interrupting it
+    thread::sleep(Duration::from_millis(500));
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while child.try_wait().unwrap().is_none() {
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("the server kept running after SIGINT");
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
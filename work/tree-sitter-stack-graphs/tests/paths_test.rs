@@ -0,0 +1,42 @@
+use std::fs;
+
+use tempfile::TempDir;
+
+mod common;
+
+use common::{run, stdout};
+
+fn create_project() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("p")).unwrap();
+    fs::write(dir.path().join("p/a.py"), "def f():\n    pass\n\nf()\n").unwrap();
+    dir
+}
+
+#[test]
+fn test_same_file_named_twice_is_built_once() {
+    let dir = create_project();
+
+    let output = run(dir.path(), &["analyze", "p/a.py", "./p/a.py", "./p"]);
+    assert!(output.status.success());
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Analyzed 1 of 1 files"), "{}", stdout);
+    assert!(!stdout.contains("./"), "{}", stdout);
+}
+
+#[test]
+fn test_positions_are_normalized() {
+    let dir = create_project();
+
+    let output = run(dir.path(), &["definition", "./p/a.py:4:1"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "p/a.py:1:5-1:6: definition of `f`\n");
+
+    let output = run(dir.path(), &["references", "./p/a.py:1:5"]);
+    assert!(output.status.success());
+    assert!(
+        stdout(&output).starts_with("p/a.py\n"),
+        "{}",
+        stdout(&output)
+    );
+}
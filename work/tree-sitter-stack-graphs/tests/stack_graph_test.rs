@@ -1,4 +1,5 @@
 use insta::assert_snapshot;
+use stack_graph_tools::Exporter;
 use stack_graphs::graph::{NodeID, StackGraph};
 
 #[test]
@@ -35,17 +36,48 @@ fn test_multiple_symbols() {
     assert_snapshot!(format_graph_info(&mut graph));
 }
 
-fn format_graph_info(graph: &mut StackGraph) -> String {
-    let mut info = String::new();
-
-    // Basic graph information
-    info.push_str(&format!("Total nodes: {}\n", graph.iter_nodes().count()));
+#[test]
+fn test_canonical_text_ignores_insertion_order() {
+    // 同じグラフを逆の順序で組み立てる
+    let build = |reversed: bool| {
+        let mut graph = StackGraph::new();
+        let mut files = vec!["a.py", "b.py"];
+        if reversed {
+            files.reverse();
+        }
+        for name in files {
+            let file = graph.get_or_create_file(name);
+            let symbol = graph.add_symbol("x");
+            let mut ids = vec![1, 2];
+            if reversed {
+                ids.reverse();
+            }
+            for local_id in ids {
+                let id = NodeID::new_in_file(file, local_id);
+                if local_id == 1 {
+                    graph.add_scope_node(id, false).unwrap();
+                } else {
+                    graph.add_pop_symbol_node(id, symbol, true).unwrap();
+                }
+            }
+            let scope = graph.node_for_id(NodeID::new_in_file(file, 1)).unwrap();
+            let definition = graph.node_for_id(NodeID::new_in_file(file, 2)).unwrap();
+            graph.add_edge(scope, definition, 0);
+            graph.add_edge(StackGraph::root_node(), scope, 1);
+        }
+        graph
+    };
 
-    // Display nodes
-    info.push_str("\nNodes:\n");
-    for node in graph.iter_nodes() {
-        info.push_str(&format!("- Node {:?}\n", node));
-    }
+    let mut graph = build(false);
+    let mut reversed = build(true);
+    assert_eq!(
+        format_graph_info(&mut graph),
+        format_graph_info(&mut reversed)
+    );
+}
 
-    info
+/// Formats the graph in the canonical textual form, which does not depend on the
+/// order in which nodes were added
+fn format_graph_info(graph: &mut StackGraph) -> String {
+    Exporter::new(graph).to_text()
 }
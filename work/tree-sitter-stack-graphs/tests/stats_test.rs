@@ -0,0 +1,122 @@
+use std::fs;
+use std::process::Command;
+
+use serde_json::Value;
+use stack_graph_tools::{Counts, GraphStats};
+use stack_graphs::graph::{NodeID, StackGraph};
+use tempfile::TempDir;
+
+/// Builds a file with a reachable definition, two references to `y` and an
+/// orphan scope
+fn graph() -> StackGraph {
+    let mut graph = StackGraph::new();
+    let file = graph.get_or_create_file("a.py");
+    let x = graph.add_symbol("x");
+    let y = graph.add_symbol("y");
+    graph.add_symbol("unused");
+
+    let root = StackGraph::root_node();
+    let scope = graph
+        .add_scope_node(NodeID::new_in_file(file, 1), false)
+        .unwrap();
+    let definition = graph
+        .add_pop_symbol_node(NodeID::new_in_file(file, 2), x, true)
+        .unwrap();
+    let first = graph
+        .add_push_symbol_node(NodeID::new_in_file(file, 3), y, true)
+        .unwrap();
+    graph
+        .add_scope_node(NodeID::new_in_file(file, 4), false)
+        .unwrap();
+    let second = graph
+        .add_push_symbol_node(NodeID::new_in_file(file, 5), y, true)
+        .unwrap();
+    graph.add_edge(root, scope, 0);
+    graph.add_edge(scope, definition, 0);
+    graph.add_edge(first, root, 0);
+    graph.add_edge(second, root, 0);
+    graph
+}
+
+#[test]
+fn test_graph_stats() {
+    let stats = GraphStats::new(&graph(), 10);
+
+    assert_eq!(
+        stats.files["a.py"],
+        Counts {
+            nodes: 5,
+            nodes_by_kind: [("pop_symbol", 1), ("push_symbol", 2), ("scope", 2)].into(),
+            edges: 3,
+            definitions: 1,
+            references: 2,
+            orphan_nodes: 1,
+            dead_end_scopes: 1,
+            symbols: 2,
+        }
+    );
+    // The jump-to node has no edges either
+    assert_eq!(stats.total.nodes, 7);
+    assert_eq!(stats.total.edges, 4);
+    assert_eq!(stats.total.orphan_nodes, 2);
+    assert_eq!(stats.symbol_table, 3);
+    assert_eq!(
+        stats.top_symbols,
+        [("y".to_string(), 2), ("x".to_string(), 1)]
+    );
+
+    let stats = GraphStats::new(&graph(), 1);
+    assert_eq!(stats.top_symbols, [("y".to_string(), 2)]);
+}
+
+#[test]
+fn test_graph_stats_text() {
+    let text = GraphStats::new(&graph(), 10).to_text();
+    assert!(
+        text.starts_with(
+            "a.py\n  nodes: 5 (pop_symbol 1, push_symbol 2, scope 2)\n  edges: 3\n  definitions: 1, references: 2\n  orphan nodes: 1, scopes without outgoing edges: 1\n  symbols: 2\ntotal\n"
+        ),
+        "{}",
+        text
+    );
+    assert!(
+        text.ends_with("symbol table: 3 symbols\ntop symbols:\n  y: 2\n  x: 1\n"),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn test_stats_command() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("main.py"),
+        "from util import helper\n\nhelper()\nhelper()\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("util.py"), "def helper():\n    pass\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tree-sitter-stack-graphs"))
+        .current_dir(dir.path())
+        .args(["stats", "--json", "--top", "1", "main.py", "util.py"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stats: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    let files = stats["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0]["file"], "main.py");
+    assert_eq!(files[1]["file"], "util.py");
+    assert!(files[0]["references"].as_u64().unwrap() >= 3);
+    assert!(files[1]["definitions"].as_u64().unwrap() >= 1);
+    assert!(files[0]["nodes_by_kind"]["push_symbol"].as_u64().unwrap() >= 3);
+    assert_eq!(stats["total"]["nodes_by_kind"]["root"], 1);
+    assert_eq!(stats["top_symbols"].as_array().unwrap().len(), 1);
+    assert!(stats["top_symbols"][0]["symbol"].is_string());
+    assert!(stats["top_symbols"][0]["count"].as_u64().unwrap() >= 3);
+}
@@ -0,0 +1,103 @@
+use std::fs;
+
+use stack_graph_tools::{BuildOptions, FileOutcome, Indexer, Position, Query};
+use tempfile::TempDir;
+
+mod common;
+
+use common::run_indexed;
+
+/// A module whose second function has a malformed parameter list
+const BROKEN: &str = "def helper():\n    return 1\n\ndef broken(:\n    pass\n\nhelper()\n";
+
+fn project() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("main.py"), BROKEN).unwrap();
+    dir
+}
+
+#[test]
+fn test_syntax_errors_fail_the_file_by_default() {
+    let dir = project();
+
+    let output = run_indexed(dir.path(), &["analyze", "main.py"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("main.py: error: missing `)` at 4:12"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_tolerant_analyze_builds_the_rest() {
+    let dir = project();
+
+    let output = run_indexed(dir.path(), &["analyze", "--tolerant", "main.py"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains("main.py: warning: missing `)` at 4:12"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("4 | def broken(:"), "{}", stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("syntax errors skipped"), "{}", stdout);
+    assert!(
+        stdout.contains("Analyzed 1 of 1 files, 0 failed"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_tolerant_build_resolves_well_formed_code() {
+    let dir = project();
+    let indexer = Indexer::new().unwrap().with_options(BuildOptions {
+        tolerant: true,
+        ..BuildOptions::default()
+    });
+
+    let analysis = indexer.build(&[dir.path().join("main.py")]).unwrap();
+    let FileOutcome::Built { syntax_errors, .. } = &analysis.reports[0].outcome else {
+        panic!("main.py was not built");
+    };
+    assert!(!syntax_errors.is_empty());
+    assert_eq!(syntax_errors[0].excerpt.line, 4);
+
+    let position = Position {
+        path: dir.path().join("main.py"),
+        line: 7,
+        column: 1,
+    };
+    let resolution = Query::new(&analysis.graph)
+        .definitions(&position)
+        .unwrap()
+        .unwrap();
+    assert_eq!(resolution.symbol, "helper");
+    assert!(
+        resolution
+            .definitions
+            .iter()
+            .any(|location| location.start_line == 1 && location.start_column == 5),
+        "{:?}",
+        resolution.definitions
+    );
+}
+
+#[test]
+fn test_tolerant_index() {
+    let dir = project();
+
+    let output = run_indexed(dir.path(), &["index", "--tolerant", "main.py"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("main.py: warning: "), "{}", stderr);
+
+    let output = run_indexed(dir.path(), &["definition", "--use-index", "main.py:7:1"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("main.py:1:5-1:11"), "{}", stdout);
+}
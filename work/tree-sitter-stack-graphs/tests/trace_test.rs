@@ -0,0 +1,98 @@
+use std::fs;
+
+use tempfile::TempDir;
+
+mod common;
+
+use common::{fixtures, run};
+
+#[test]
+fn test_trace_resolved_reference() {
+    let output = run(
+        &fixtures().join("python"),
+        &["trace", "--json", "functions.py:10:12"],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let trace: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(trace["symbol"], "greet");
+    assert_eq!(trace["reference"]["file"], "functions.py");
+    assert_eq!(trace["definitions"].as_array().unwrap().len(), 1);
+    // The first step pushes the referenced symbol
+    assert_eq!(trace["steps"][0]["symbol_stack"][0], "greet");
+}
+
+#[test]
+fn test_trace_unresolved_reference() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("main.py"), "value = 1\nmissing\n").unwrap();
+
+    let output = run(dir.path(), &["trace", "--json", "main.py:2:1"]);
+    assert!(!output.status.success());
+    let trace: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(trace["definitions"].as_array().unwrap().is_empty());
+
+    // The definition of `value` is where the symbol stack stopped matching
+    let stops = trace["stops"].as_array().unwrap();
+    let stop = stops
+        .iter()
+        .find(|stop| {
+            stop["reason"]
+                .as_str()
+                .unwrap()
+                .contains("`value` is not on top of the symbol stack")
+        })
+        .unwrap_or_else(|| panic!("{:#}", trace));
+    assert_eq!(stop["symbol_stack"][0], "missing");
+}
+
+#[test]
+fn test_trace_dot_overlay() {
+    let output = run(&fixtures().join("python"), &["trace", "functions.py:10:12"]);
+    assert!(output.status.success());
+    let dot = String::from_utf8_lossy(&output.stdout);
+
+    assert!(dot.contains("subgraph \"cluster_functions.py\""), "{}", dot);
+    assert!(dot.contains("// Overlay"), "{}", dot);
+    assert!(dot.contains("[color=\"blue\", penwidth=3];"), "{}", dot);
+    assert!(dot.contains("[color=\"green\", penwidth=3];"), "{}", dot);
+    assert!(dot.contains("symbols: greet"), "{}", dot);
+
+    // Overlay edges restyle the graph's edges instead of adding new ones
+    assert!(dot.starts_with("strict digraph "), "{}", dot);
+    let overlay = &dot[dot.find("// Overlay").unwrap()..];
+    let mut edges = overlay
+        .lines()
+        .filter_map(|line| line.trim().split_once(" ["))
+        .map(|(edge, _)| edge)
+        .filter(|edge| edge.contains("->"))
+        .collect::<Vec<_>>();
+    let count = edges.len();
+    edges.sort();
+    edges.dedup();
+    assert_eq!(edges.len(), count, "{}", overlay);
+}
+
+#[test]
+fn test_trace_leaves_out_shadowed_builtins() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("main.py"),
+        "def len(x):\n    return 0\n\nlen([])\n",
+    )
+    .unwrap();
+
+    let output = run(
+        dir.path(),
+        &["--builtins", "trace", "--json", "main.py:4:1"],
+    );
+    assert!(output.status.success());
+    let trace: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let definitions = trace["definitions"].as_array().unwrap();
+    assert_eq!(definitions.len(), 1, "{:#}", trace);
+    assert_eq!(definitions[0]["file"], "main.py");
+}
@@ -0,0 +1,99 @@
+use std::fs;
+
+use tempfile::TempDir;
+
+mod common;
+
+use common::{run, stdout};
+
+/// Creates a project in which `main.py` has two unresolved references
+fn project() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("project")).unwrap();
+    fs::write(
+        dir.path().join("project/main.py"),
+        "from util import helper\n\nhelper()\nmissing()\n\ndef run():\n    return other\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("project/util.py"),
+        "def helper():\n    pass\n",
+    )
+    .unwrap();
+    dir
+}
+
+#[test]
+fn test_unresolved_text_report() {
+    let dir = project();
+
+    let output = run(dir.path(), &["unresolved", "project"]);
+    assert!(!output.status.success());
+    let report = stdout(&output);
+    assert!(
+        report.contains("project/main.py\n  4:1: `missing`\n  7:12: `other`\n"),
+        "{}",
+        report
+    );
+    assert!(!report.contains("util.py"), "{}", report);
+    assert!(
+        report.contains("Found 2 unresolved references in 1 files"),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn test_unresolved_json_report() {
+    let dir = project();
+
+    let output = run(dir.path(), &["unresolved", "--json", "project"]);
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["unresolved"], 2);
+    assert_eq!(report["files"][0]["file"], "project/main.py");
+    assert_eq!(
+        report["files"][0]["references"][0],
+        serde_json::json!({
+            "symbol": "missing",
+            "line": 4,
+            "column": 1,
+            "end_line": 4,
+            "end_column": 8
+        })
+    );
+    assert_eq!(report["files"][0]["references"][1]["symbol"], "other");
+}
+
+#[test]
+fn test_resolved_project_succeeds() {
+    let dir = project();
+    fs::write(
+        dir.path().join("project/main.py"),
+        "from util import helper\n\nhelper()\n",
+    )
+    .unwrap();
+
+    let output = run(dir.path(), &["unresolved", "project"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(
+        stdout(&output).contains("Found 0 unresolved references in 0 files"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn test_unresolved_from_index() {
+    let dir = project();
+    assert!(run(dir.path(), &["index", "project"]).status.success());
+
+    let output = run(dir.path(), &["unresolved", "--use-index"]);
+    assert!(!output.status.success());
+    let report = stdout(&output);
+    assert!(
+        report.contains("project/main.py\n  4:1: `missing`\n  7:12: `other`\n"),
+        "{}",
+        report
+    );
+}